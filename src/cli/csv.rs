@@ -1,42 +1,1102 @@
 use super::verify_file;
-use crate::{process_csv, CmdExcutor};
+use crate::{
+    process_csv, process_csv_agg, process_csv_codegen, process_csv_decrypt, process_csv_diff,
+    process_csv_encrypt, process_csv_fake, process_csv_from, process_csv_join, process_csv_mask,
+    process_csv_merge, process_csv_query, process_csv_schema, process_csv_show, process_csv_split,
+    process_csv_stats, process_csv_to_sqlite, process_csv_validate, utils::get_writer, AggOptions,
+    CmdExcutor, ConvertOptions, CryptOptions, CsvDialect, JoinOptions, MaskOptions, RowWindow,
+    SplitBy, SqliteOptions, TransformRule, TypeHints,
+};
 use anyhow::Result;
-use clap::Parser;
-use std::{fmt::Display, str::FromStr};
+use clap::{ArgAction, Args, Parser};
+use encoding_rs::Encoding;
+use enum_dispatch::enum_dispatch;
+use std::{fmt::Display, io::Write, path::Path, str::FromStr};
 
 #[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     Json,
     Yaml,
-    // Toml,
+    Toml,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum InputFormat {
+    Json,
+    Yaml,
+    Ndjson,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayMode {
+    Json,
+    Explode,
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShowStyle {
+    Table,
+    Markdown,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Sum,
+    Avg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStyle {
+    Human,
+    Json,
+    Patch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    Array,
+    Keyed,
+    Columnar,
+    Arrays,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodegenLang {
+    Rust,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    #[default]
+    Fail,
+    Skip,
+    Report,
 }
 
 #[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct CsvOpts {
-    #[arg(short, long, value_parser = verify_file)]
-    pub input: String,
+    #[command(subcommand)]
+    pub cmd: Option<CsvSubcommand>,
+
+    #[command(flatten)]
+    pub convert: CsvConvertOpts,
+}
+
+#[derive(Debug, Parser)]
+#[enum_dispatch(CmdExcutor)]
+pub enum CsvSubcommand {
+    #[command(name = "from-json", about = "Convert a json array of objects to csv")]
+    FromJson(CsvFromJsonOpts),
+    #[command(name = "from-yaml", about = "Convert a yaml list of mappings to csv")]
+    FromYaml(CsvFromYamlOpts),
+    #[command(name = "from-ndjson", about = "Convert newline delimited json to csv")]
+    FromNdjson(CsvFromNdjsonOpts),
+    #[command(about = "Show csv as a table")]
+    Show(CsvShowOpts),
+    #[command(about = "Infer a json schema from csv")]
+    Schema(CsvSchemaOpts),
+    #[command(about = "Validate csv rows against a json schema")]
+    Validate(CsvValidateOpts),
+    #[command(about = "Group rows and aggregate columns")]
+    Agg(CsvAggOpts),
+    #[command(about = "Join two csv files on key columns")]
+    Join(CsvJoinOpts),
+    #[command(about = "Profile every column of a csv file")]
+    Stats(CsvStatsOpts),
+    #[command(about = "Compare two versions of a csv file row by row")]
+    Diff(CsvDiffOpts),
+    #[command(about = "Query csv files with sql")]
+    Query(CsvQueryOpts),
+    #[command(about = "Split a csv file into parts by rows, size or column value")]
+    Split(CsvSplitOpts),
+    #[command(about = "Concatenate csv files, reconciling their headers")]
+    Merge(CsvMergeOpts),
+    #[command(about = "Pseudonymize, redact or scramble columns")]
+    Mask(CsvMaskOpts),
+    #[command(about = "Encrypt columns cell by cell with chacha20-poly1305")]
+    Encrypt(CsvEncryptOpts),
+    #[command(about = "Decrypt columns encrypted by csv encrypt")]
+    Decrypt(CsvDecryptOpts),
+    #[command(about = "Load csv into a table of a sqlite database file")]
+    ToSqlite(CsvToSqliteOpts),
+    #[command(about = "Generate a type for the rows of a csv file")]
+    Codegen(CsvCodegenOpts),
+    #[command(about = "Generate synthetic csv rows from a yaml schema")]
+    Fake(CsvFakeOpts),
+}
 
-    #[arg(short, long)]
+#[derive(Debug, Args)]
+pub struct CsvConvertOpts {
+    #[arg(short, long, value_parser = verify_file, required = true)]
+    pub input: Option<String>,
+
+    #[arg(
+        short,
+        long,
+        help = "Output file, - for stdout [default: output.<format>]"
+    )]
     pub output: Option<String>,
 
     #[arg(long, value_parser = parse_format, default_value = "json")]
     pub format: OutputFormat,
 
-    #[arg(short, long, default_value_t = ',')]
-    pub delimiter: char,
+    #[arg(
+        long,
+        default_value = "rows",
+        help = "Array-of-tables key the rows are written under in toml output"
+    )]
+    pub toml_key: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(long, help = "Infer int/float/bool/null values for every column")]
+    pub infer: bool,
+
+    #[arg(
+        long = "type",
+        value_parser = parse_column_type,
+        help = "Column type override, e.g. \"Kit Number=int\""
+    )]
+    pub types: Vec<(String, ColumnType)>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Only output these columns, in this order"
+    )]
+    pub select: Vec<String>,
+
+    #[arg(long, value_parser = parse_rename, help = "Rename a column, e.g. \"Kit Number=kit\"")]
+    pub rename: Vec<(String, String)>,
+
+    #[arg(
+        long = "where",
+        help = "Only output rows matching, e.g. 'Position == \"Goalkeeper\" && Kit Number > 10'"
+    )]
+    pub filter: Option<String>,
+
+    #[arg(
+        long,
+        help = "Build nested objects and arrays from headers like address.city and tags[0]"
+    )]
+    pub nested: bool,
+
+    #[arg(
+        long = "transform",
+        value_parser = parse_transform,
+        help = "Transform a column, e.g. \"DOB:date=%b %d, %Y\", \"Name:trim\", \"Name:upper\", \
+                \"Name:replace=/ +/_/\", \"Name:split=/ /First,Last/\", or derive one, e.g. \
+                \"Double={Kit Number} * 2\""
+    )]
+    pub transforms: Vec<TransformRule>,
+
+    #[arg(
+        long,
+        value_parser = parse_shape,
+        default_value = "array",
+        help = "Output layout: array of row objects, keyed by --key-by, columnar, or arrays \
+                of rows under a header"
+    )]
+    pub shape: Shape,
+
+    #[arg(long, help = "Output column to key rows by with --shape keyed")]
+    pub key_by: Option<String>,
+
+    #[arg(
+        long,
+        value_parser = parse_on_error,
+        default_value = "fail",
+        help = "What to do with rows that don't parse or convert: fail, skip, or report them"
+    )]
+    pub on_error: OnError,
+
+    #[arg(
+        long,
+        default_value = "rejected.csv",
+        help = "Where --on-error report writes the rejected rows"
+    )]
+    pub rejects: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CsvDialectOpts {
+    #[arg(short, long, value_parser = parse_byte, default_value = ",")]
+    pub delimiter: u8,
 
-    #[arg(long, default_value_t = true)]
+    #[arg(long, action = ArgAction::Set, default_value_t = true)]
     pub header: bool,
+
+    #[arg(long, value_parser = parse_byte, default_value = "\"")]
+    pub quote: u8,
+
+    #[arg(long, value_parser = parse_byte)]
+    pub escape: Option<u8>,
+
+    #[arg(long, value_parser = parse_byte)]
+    pub comment: Option<u8>,
+
+    #[arg(long, help = "Allow records with a varying number of fields")]
+    pub flexible: bool,
+
+    #[arg(
+        long,
+        help = "Detect the dialect from a sample of the input, each input on its own"
+    )]
+    pub sniff: bool,
+
+    #[arg(
+        long,
+        value_parser = parse_encoding,
+        help = "Input encoding, e.g. utf-16le, gbk or windows-1252 [default: detected]"
+    )]
+    pub encoding: Option<&'static Encoding>,
+}
+
+#[derive(Debug, Args)]
+pub struct CsvFromOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "output.csv")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = parse_byte, default_value = ",")]
+    pub delimiter: u8,
+
+    #[arg(
+        long,
+        value_parser = parse_array_mode,
+        default_value = "json",
+        help = "Write arrays as json in one cell, explode them into one row per element, or index them into tags[0], tags[1].. columns"
+    )]
+    pub arrays: ArrayMode,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromJsonOpts {
+    #[command(flatten)]
+    pub opts: CsvFromOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromYamlOpts {
+    #[command(flatten)]
+    pub opts: CsvFromOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFromNdjsonOpts {
+    #[command(flatten)]
+    pub opts: CsvFromOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvShowOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(long, conflicts_with = "tail", help = "Only show the first N rows")]
+    pub head: Option<usize>,
+
+    #[arg(long, help = "Only show the last N rows")]
+    pub tail: Option<usize>,
+
+    #[arg(long, value_parser = parse_show_style, default_value = "table")]
+    pub style: ShowStyle,
+
+    #[arg(
+        long,
+        default_value_t = 40,
+        help = "Truncate wider cells, 0 to disable"
+    )]
+    pub max_width: usize,
+
+    #[arg(long)]
+    pub no_pager: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSchemaOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(
+        long,
+        default_value_t = 10,
+        help = "Most distinct values a column can have to become an enum"
+    )]
+    pub max_enum: usize,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvValidateOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, value_parser = verify_file)]
+    pub schema: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvAggOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(long, value_delimiter = ',', help = "Columns to group rows by")]
+    pub group_by: Vec<String>,
+
+    #[arg(long, help = "Count the rows in each group (the default)")]
+    pub count: bool,
+
+    #[arg(
+        long,
+        help = "Smallest value of a column, by number, --date-format date or text"
+    )]
+    pub min: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Largest value of a column, by number, --date-format date or text"
+    )]
+    pub max: Vec<String>,
+
+    #[arg(long, help = "Sum of a numeric column")]
+    pub sum: Vec<String>,
+
+    #[arg(long, help = "Mean of a numeric column")]
+    pub avg: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Read --min/--max cells that aren't numbers as dates, e.g. \"%b %d, %Y\""
+    )]
+    pub date_format: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvJoinOpts {
+    #[arg(value_parser = verify_file)]
+    pub left: String,
+
+    #[arg(value_parser = verify_file)]
+    pub right: String,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Key columns, named the same in both files"
+    )]
+    pub on: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with = "on",
+        requires = "right_on"
+    )]
+    pub left_on: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        conflicts_with = "on",
+        requires = "left_on"
+    )]
+    pub right_on: Vec<String>,
+
+    #[arg(long, value_parser = parse_join_kind, default_value = "inner")]
+    pub how: JoinKind,
+
+    #[arg(long, default_value = "left_")]
+    pub left_prefix: String,
+
+    #[arg(long, default_value = "right_")]
+    pub right_prefix: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_format, help = "Write records in this format instead of csv")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvStatsOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "How many of the most frequent values to list"
+    )]
+    pub top: usize,
+
+    #[arg(long, help = "Write the report as json instead of a table")]
+    pub json: bool,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDiffOpts {
+    #[arg(value_parser = verify_file)]
+    pub old: String,
+
+    #[arg(value_parser = verify_file)]
+    pub new: String,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        required = true,
+        help = "Columns that identify a row in both files"
+    )]
+    pub key: Vec<String>,
+
+    #[arg(long, value_parser = parse_diff_style, default_value = "human")]
+    pub style: DiffStyle,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvQueryOpts {
+    #[arg(help = "e.g. \"SELECT Nationality, COUNT(*) FROM players GROUP BY Nationality\"")]
+    pub sql: String,
+
+    #[arg(
+        short,
+        long = "table",
+        value_parser = parse_table,
+        required = true,
+        help = "A csv file to query, as NAME=PATH or just PATH to name it after the file"
+    )]
+    pub tables: Vec<(String, String)>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_format, default_value = "json")]
+    pub format: OutputFormat,
+
+    #[arg(long, default_value = "rows")]
+    pub toml_key: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvSplitOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[command(flatten)]
+    pub by: CsvSplitByOpts,
+
+    #[arg(long, default_value = ".", help = "Directory to write the parts to")]
+    pub out_dir: String,
+
+    #[arg(
+        long,
+        help = "File name prefix for the parts [default: input file name]"
+    )]
+    pub prefix: Option<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true, multiple = false)]
+pub struct CsvSplitByOpts {
+    #[arg(long, help = "Rows per part")]
+    pub rows: Option<usize>,
+
+    #[arg(long, value_parser = parse_size, help = "Largest part size, e.g. 500k or 10M")]
+    pub bytes: Option<u64>,
+
+    #[arg(long, help = "Write one part per distinct value of this column")]
+    pub by: Option<String>,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMergeOpts {
+    #[arg(value_parser = verify_file, required = true)]
+    pub inputs: Vec<String>,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvMaskOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(
+        short,
+        long,
+        value_parser = verify_file,
+        help = "Blake3 key file, needed by --column and --preserve"
+    )]
+    pub key: Option<String>,
+
+    #[arg(
+        long = "column",
+        value_delimiter = ',',
+        help = "Replace values with keyed pseudonyms, equal values stay equal"
+    )]
+    pub pseudonymize: Vec<String>,
+
+    #[arg(long, value_delimiter = ',', help = "Blank these columns")]
+    pub redact: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Scramble letters and digits, keeping the format"
+    )]
+    pub preserve: Vec<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Args)]
+pub struct CsvCryptArgs {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(short, long, value_parser = verify_file, help = "Chacha20-poly1305 key file")]
+    pub key: String,
+
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub columns: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Bind cells to this column's value instead of the row number, so rows may be \
+                reordered in between"
+    )]
+    pub row_key: Option<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvEncryptOpts {
+    #[command(flatten)]
+    pub args: CsvCryptArgs,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvDecryptOpts {
+    #[command(flatten)]
+    pub args: CsvCryptArgs,
+}
+
+impl CsvCryptArgs {
+    fn options(&self) -> CryptOptions {
+        CryptOptions {
+            columns: self.columns.clone(),
+            row_key: self.row_key.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvToSqliteOpts {
+    #[arg(help = "Database file, created if it doesn't exist")]
+    pub database: String,
+
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(long, help = "Table to create [default: input file name]")]
+    pub table: Option<String>,
+
+    #[arg(long = "index", value_delimiter = ',', help = "Columns to index")]
+    pub indexes: Vec<String>,
+
+    #[arg(long, help = "Replace the table if it already exists")]
+    pub replace: bool,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvCodegenOpts {
+    #[arg(short, long, value_parser = verify_file, default_value = "-")]
+    pub input: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, value_parser = parse_codegen_lang, default_value = "rust")]
+    pub lang: CodegenLang,
+
+    #[arg(long, help = "Type name [default: input file name, or Record]")]
+    pub name: Option<String>,
+
+    #[command(flatten)]
+    pub dialect: CsvDialectOpts,
+}
+
+#[derive(Debug, Parser)]
+pub struct CsvFakeOpts {
+    #[arg(long, value_parser = verify_file, help = "Yaml file describing the columns")]
+    pub schema: String,
+
+    #[arg(short, long, default_value = "-")]
+    pub output: String,
+
+    #[arg(long, default_value_t = 100)]
+    pub rows: u64,
+
+    #[arg(long, help = "Seed for a reproducible run [default: random, printed]")]
+    pub seed: Option<u64>,
+
+    #[arg(short, long, value_parser = parse_byte, default_value = ",")]
+    pub delimiter: u8,
+}
+
+impl CsvDialectOpts {
+    pub fn to_dialect(&self, input: &str) -> Result<CsvDialect> {
+        if self.sniff {
+            return CsvDialect::sniff_path(input, self.encoding);
+        }
+        Ok(CsvDialect {
+            delimiter: self.delimiter,
+            quote: self.quote,
+            escape: self.escape,
+            comment: self.comment,
+            flexible: self.flexible,
+            has_headers: self.header,
+            encoding: self.encoding,
+        })
+    }
 }
 
 impl CmdExcutor for CsvOpts {
     async fn execute(self) -> anyhow::Result<()> {
+        match self.cmd {
+            Some(cmd) => cmd.execute().await,
+            None => self.convert.execute().await,
+        }
+    }
+}
+
+impl CmdExcutor for Box<CsvOpts> {
+    async fn execute(self) -> anyhow::Result<()> {
+        (*self).execute().await
+    }
+}
+
+impl CmdExcutor for CsvConvertOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let Some(input) = self.input else {
+            anyhow::bail!("--input is required");
+        };
         let output = if let Some(output) = self.output {
             output
         } else {
             format!("output.{}", self.format)
         };
-        process_csv(&self.input, output, self.format)
+        let dialect = self.dialect.to_dialect(&input)?;
+        let opts = ConvertOptions {
+            types: TypeHints {
+                infer: self.infer,
+                overrides: self.types,
+            },
+            toml_key: self.toml_key,
+            select: self.select,
+            rename: self.rename,
+            filter: self.filter,
+            nested: self.nested,
+            transforms: self.transforms,
+            shape: self.shape,
+            key_by: self.key_by,
+            on_error: self.on_error,
+            rejects: self.rejects,
+        };
+        process_csv(&input, &output, self.format, &dialect, &opts)
+    }
+}
+
+impl CsvFromOpts {
+    fn run(self, format: InputFormat) -> Result<()> {
+        process_csv_from(
+            &self.input,
+            &self.output,
+            format,
+            self.arrays,
+            self.delimiter,
+        )
+    }
+}
+
+impl CmdExcutor for CsvFromJsonOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        self.opts.run(InputFormat::Json)
+    }
+}
+
+impl CmdExcutor for CsvFromYamlOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        self.opts.run(InputFormat::Yaml)
+    }
+}
+
+impl CmdExcutor for CsvFromNdjsonOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        self.opts.run(InputFormat::Ndjson)
+    }
+}
+
+impl CmdExcutor for CsvShowOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let window = match (self.head, self.tail) {
+            (Some(n), _) => RowWindow::Head(n),
+            (_, Some(n)) => RowWindow::Tail(n),
+            _ => RowWindow::All,
+        };
+        process_csv_show(
+            &self.input,
+            &dialect,
+            window,
+            self.style,
+            self.max_width,
+            !self.no_pager,
+        )
+    }
+}
+
+impl CmdExcutor for CsvSchemaOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let schema = process_csv_schema(&self.input, &dialect, self.max_enum)?;
+        let mut writer = get_writer(&self.output)?;
+        serde_json::to_writer_pretty(&mut writer, &schema)?;
+        writeln!(writer)?;
+        Ok(())
+    }
+}
+
+impl CmdExcutor for CsvValidateOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let violations = process_csv_validate(&self.input, &dialect, &self.schema)?;
+        for v in &violations {
+            println!("line {}, column {:?}: {}", v.line, v.column, v.message);
+        }
+        if !violations.is_empty() {
+            anyhow::bail!("{} schema violations", violations.len());
+        }
+        Ok(())
+    }
+}
+
+impl CmdExcutor for CsvAggOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let aggregates = [
+            (Aggregate::Min, self.min),
+            (Aggregate::Max, self.max),
+            (Aggregate::Sum, self.sum),
+            (Aggregate::Avg, self.avg),
+        ]
+        .into_iter()
+        .flat_map(|(agg, columns)| columns.into_iter().map(move |c| (agg, c)))
+        .collect();
+        let opts = AggOptions {
+            group_by: self.group_by,
+            count: self.count,
+            aggregates,
+            date_format: self.date_format,
+        };
+        process_csv_agg(
+            &self.input,
+            &self.output,
+            self.format,
+            &self.toml_key,
+            &dialect,
+            &opts,
+        )
+    }
+}
+
+impl CmdExcutor for CsvJoinOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let left = self.dialect.to_dialect(&self.left)?;
+        let right = self.dialect.to_dialect(&self.right)?;
+        let (left_on, right_on) = if self.on.is_empty() {
+            (self.left_on, self.right_on)
+        } else {
+            (self.on.clone(), self.on)
+        };
+        if left_on.is_empty() {
+            anyhow::bail!("Use --on, or --left-on with --right-on");
+        }
+        let opts = JoinOptions {
+            kind: self.how,
+            left_on,
+            right_on,
+            left_prefix: self.left_prefix,
+            right_prefix: self.right_prefix,
+        };
+        process_csv_join(
+            &self.left,
+            &self.right,
+            &self.output,
+            self.format,
+            &self.toml_key,
+            [&left, &right],
+            &opts,
+        )
+    }
+}
+
+impl CmdExcutor for CsvStatsOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        process_csv_stats(&self.input, &self.output, &dialect, self.top, self.json)
+    }
+}
+
+impl CmdExcutor for CsvDiffOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let old = self.dialect.to_dialect(&self.old)?;
+        let new = self.dialect.to_dialect(&self.new)?;
+        process_csv_diff(
+            &self.old,
+            &self.new,
+            &self.key,
+            &self.output,
+            self.style,
+            [&old, &new],
+        )
+    }
+}
+
+impl CmdExcutor for CsvQueryOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialects = self
+            .tables
+            .iter()
+            .map(|(_, path)| self.dialect.to_dialect(path))
+            .collect::<Result<Vec<_>>>()?;
+        process_csv_query(
+            &self.sql,
+            &self.tables,
+            &self.output,
+            self.format,
+            &self.toml_key,
+            &dialects,
+        )
+    }
+}
+
+impl CmdExcutor for CsvSplitOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let by = match self.by {
+            CsvSplitByOpts { rows: Some(0), .. } => anyhow::bail!("--rows must be at least 1"),
+            CsvSplitByOpts { rows: Some(n), .. } => SplitBy::Rows(n),
+            CsvSplitByOpts { bytes: Some(n), .. } => SplitBy::Bytes(n),
+            CsvSplitByOpts { by: Some(col), .. } => SplitBy::Column(col),
+            _ => anyhow::bail!("Use --rows, --bytes or --by"),
+        };
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        process_csv_split(
+            &self.input,
+            &by,
+            &self.out_dir,
+            self.prefix.as_deref(),
+            &dialect,
+        )
+    }
+}
+
+impl CmdExcutor for CsvMergeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialects = self
+            .inputs
+            .iter()
+            .map(|input| self.dialect.to_dialect(input))
+            .collect::<Result<Vec<_>>>()?;
+        process_csv_merge(&self.inputs, &self.output, &dialects)
+    }
+}
+
+impl CmdExcutor for CsvMaskOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let opts = MaskOptions {
+            pseudonymize: self.pseudonymize,
+            redact: self.redact,
+            preserve: self.preserve,
+        };
+        process_csv_mask(
+            &self.input,
+            &self.output,
+            self.key.as_deref(),
+            &dialect,
+            &opts,
+        )
+    }
+}
+
+impl CmdExcutor for CsvEncryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let args = self.args;
+        let dialect = args.dialect.to_dialect(&args.input)?;
+        process_csv_encrypt(
+            &args.input,
+            &args.output,
+            &args.key,
+            &dialect,
+            &args.options(),
+        )
+    }
+}
+
+impl CmdExcutor for CsvDecryptOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let args = self.args;
+        let dialect = args.dialect.to_dialect(&args.input)?;
+        process_csv_decrypt(
+            &args.input,
+            &args.output,
+            &args.key,
+            &dialect,
+            &args.options(),
+        )
+    }
+}
+
+impl CmdExcutor for CsvToSqliteOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let table = match self.table {
+            Some(table) => table,
+            None => {
+                let stem = Path::new(&self.input)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str());
+                match stem {
+                    Some(stem) if self.input != "-" => stem.to_string(),
+                    _ => anyhow::bail!("Name the table with --table"),
+                }
+            }
+        };
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let opts = SqliteOptions {
+            table,
+            indexes: self.indexes,
+            replace: self.replace,
+        };
+        process_csv_to_sqlite(&self.input, &self.database, &dialect, &opts)
+    }
+}
+
+impl CmdExcutor for CsvCodegenOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        let name = match self.name {
+            Some(name) => name,
+            None if self.input == "-" => "Record".to_string(),
+            None => Path::new(&self.input)
+                .file_stem()
+                .map_or("Record".into(), |stem| stem.to_string_lossy().to_string()),
+        };
+        let dialect = self.dialect.to_dialect(&self.input)?;
+        let code = process_csv_codegen(&self.input, &dialect, self.lang, &name)?;
+        get_writer(&self.output)?.write_all(code.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl CmdExcutor for CsvFakeOpts {
+    async fn execute(self) -> anyhow::Result<()> {
+        process_csv_fake(
+            &self.schema,
+            &self.output,
+            self.rows,
+            self.seed,
+            self.delimiter,
+        )
     }
 }
 
@@ -44,12 +1104,104 @@ fn parse_format(format: &str) -> Result<OutputFormat, anyhow::Error> {
     format.parse()
 }
 
+fn parse_join_kind(kind: &str) -> Result<JoinKind, anyhow::Error> {
+    kind.parse()
+}
+
+fn parse_diff_style(style: &str) -> Result<DiffStyle, anyhow::Error> {
+    style.parse()
+}
+
+fn parse_codegen_lang(lang: &str) -> Result<CodegenLang, anyhow::Error> {
+    lang.parse()
+}
+
+fn parse_shape(shape: &str) -> Result<Shape, anyhow::Error> {
+    shape.parse()
+}
+
+fn parse_on_error(mode: &str) -> Result<OnError, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_show_style(style: &str) -> Result<ShowStyle, anyhow::Error> {
+    style.parse()
+}
+
+fn parse_array_mode(mode: &str) -> Result<ArrayMode, anyhow::Error> {
+    mode.parse()
+}
+
+fn parse_column_type(s: &str) -> Result<(String, ColumnType), anyhow::Error> {
+    let Some((name, ty)) = s.rsplit_once('=') else {
+        anyhow::bail!("Expected COLUMN=TYPE, got: {}", s);
+    };
+    Ok((name.to_string(), ty.parse()?))
+}
+
+fn parse_rename(s: &str) -> Result<(String, String), anyhow::Error> {
+    let Some((from, to)) = s.rsplit_once('=') else {
+        anyhow::bail!("Expected OLD=NEW, got: {}", s);
+    };
+    Ok((from.to_string(), to.to_string()))
+}
+
+fn parse_encoding(label: &str) -> Result<&'static Encoding, anyhow::Error> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| anyhow::anyhow!("Unsupported encoding: {}", label))
+}
+
+fn parse_table(s: &str) -> Result<(String, String), anyhow::Error> {
+    let (name, path) = match s.split_once('=') {
+        Some((name, path)) => (name.to_string(), path),
+        None => {
+            let stem = Path::new(s).file_stem().and_then(|stem| stem.to_str());
+            let Some(stem) = stem else {
+                anyhow::bail!("Can't name a table after {}, use NAME=PATH", s);
+            };
+            (stem.to_string(), s)
+        }
+    };
+    Ok((name, verify_file(path).map_err(anyhow::Error::msg)?))
+}
+
+fn parse_size(s: &str) -> Result<u64, anyhow::Error> {
+    let lower = s.trim().to_lowercase();
+    let digits = lower.trim_end_matches(['b', 'i']);
+    let (digits, unit) = match digits.char_indices().last() {
+        Some((i, 'k')) => (&digits[..i], 1 << 10),
+        Some((i, 'm')) => (&digits[..i], 1 << 20),
+        Some((i, 'g')) => (&digits[..i], 1 << 30),
+        _ => (digits, 1),
+    };
+    match digits.trim().parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n * unit),
+        _ => anyhow::bail!("Invalid size {}, use e.g. 500k or 10M", s),
+    }
+}
+
+fn parse_transform(s: &str) -> Result<TransformRule, anyhow::Error> {
+    s.parse()
+}
+
+fn parse_byte(s: &str) -> Result<u8, anyhow::Error> {
+    let s = match s {
+        "\\t" | "tab" => "\t",
+        s => s,
+    };
+    match s.as_bytes() {
+        [b] if b.is_ascii() => Ok(*b),
+        _ => anyhow::bail!("Expected a single ascii character, got: {}", s),
+    }
+}
+
 impl From<OutputFormat> for &'static str {
     fn from(format: OutputFormat) -> Self {
         match format {
             OutputFormat::Json => "json",
             OutputFormat::Yaml => "yaml",
-            // OutputFormat::Toml => "toml",
+            OutputFormat::Toml => "toml",
+            OutputFormat::Ndjson => "ndjson",
         }
     }
 }
@@ -60,7 +1212,8 @@ impl FromStr for OutputFormat {
         match format.to_lowercase().as_str() {
             "json" => Ok(OutputFormat::Json),
             "yaml" => Ok(OutputFormat::Yaml),
-            // "toml" => Ok(OutputFormat::Toml),
+            "toml" => Ok(OutputFormat::Toml),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
             v => anyhow::bail!("Unsupported format: {}", v),
         }
     }
@@ -71,3 +1224,246 @@ impl Display for OutputFormat {
         write!(f, "{}", Into::<&str>::into(*self))
     }
 }
+
+impl From<ColumnType> for &'static str {
+    fn from(ty: ColumnType) -> Self {
+        match ty {
+            ColumnType::String => "string",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Bool => "bool",
+        }
+    }
+}
+
+impl FromStr for ColumnType {
+    type Err = anyhow::Error;
+    fn from_str(ty: &str) -> Result<Self, Self::Err> {
+        match ty.to_lowercase().as_str() {
+            "string" | "str" => Ok(ColumnType::String),
+            "int" | "integer" => Ok(ColumnType::Int),
+            "float" | "number" => Ok(ColumnType::Float),
+            "bool" | "boolean" => Ok(ColumnType::Bool),
+            v => anyhow::bail!("Unsupported column type: {}", v),
+        }
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<ArrayMode> for &'static str {
+    fn from(mode: ArrayMode) -> Self {
+        match mode {
+            ArrayMode::Json => "json",
+            ArrayMode::Explode => "explode",
+            ArrayMode::Index => "index",
+        }
+    }
+}
+
+impl FromStr for ArrayMode {
+    type Err = anyhow::Error;
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "json" => Ok(ArrayMode::Json),
+            "explode" => Ok(ArrayMode::Explode),
+            "index" => Ok(ArrayMode::Index),
+            v => anyhow::bail!("Unsupported array mode: {}", v),
+        }
+    }
+}
+
+impl Display for ArrayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<ShowStyle> for &'static str {
+    fn from(style: ShowStyle) -> Self {
+        match style {
+            ShowStyle::Table => "table",
+            ShowStyle::Markdown => "markdown",
+            ShowStyle::Html => "html",
+        }
+    }
+}
+
+impl FromStr for ShowStyle {
+    type Err = anyhow::Error;
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style.to_lowercase().as_str() {
+            "table" => Ok(ShowStyle::Table),
+            "markdown" | "md" => Ok(ShowStyle::Markdown),
+            "html" => Ok(ShowStyle::Html),
+            v => anyhow::bail!("Unsupported style: {}", v),
+        }
+    }
+}
+
+impl Display for ShowStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<Aggregate> for &'static str {
+    fn from(agg: Aggregate) -> Self {
+        match agg {
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Sum => "sum",
+            Aggregate::Avg => "avg",
+        }
+    }
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<JoinKind> for &'static str {
+    fn from(kind: JoinKind) -> Self {
+        match kind {
+            JoinKind::Inner => "inner",
+            JoinKind::Left => "left",
+            JoinKind::Right => "right",
+            JoinKind::Full => "full",
+        }
+    }
+}
+
+impl FromStr for JoinKind {
+    type Err = anyhow::Error;
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind.to_lowercase().as_str() {
+            "inner" => Ok(JoinKind::Inner),
+            "left" => Ok(JoinKind::Left),
+            "right" => Ok(JoinKind::Right),
+            "full" | "outer" => Ok(JoinKind::Full),
+            v => anyhow::bail!("Unsupported join: {}", v),
+        }
+    }
+}
+
+impl Display for JoinKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<DiffStyle> for &'static str {
+    fn from(style: DiffStyle) -> Self {
+        match style {
+            DiffStyle::Human => "human",
+            DiffStyle::Json => "json",
+            DiffStyle::Patch => "patch",
+        }
+    }
+}
+
+impl FromStr for DiffStyle {
+    type Err = anyhow::Error;
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        match style.to_lowercase().as_str() {
+            "human" | "text" => Ok(DiffStyle::Human),
+            "json" => Ok(DiffStyle::Json),
+            "patch" | "csv" => Ok(DiffStyle::Patch),
+            v => anyhow::bail!("Unsupported diff style: {}", v),
+        }
+    }
+}
+
+impl Display for DiffStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<OnError> for &'static str {
+    fn from(mode: OnError) -> Self {
+        match mode {
+            OnError::Fail => "fail",
+            OnError::Skip => "skip",
+            OnError::Report => "report",
+        }
+    }
+}
+
+impl FromStr for OnError {
+    type Err = anyhow::Error;
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "fail" => Ok(OnError::Fail),
+            "skip" => Ok(OnError::Skip),
+            "report" => Ok(OnError::Report),
+            v => anyhow::bail!("Unsupported error mode: {}", v),
+        }
+    }
+}
+
+impl Display for OnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<Shape> for &'static str {
+    fn from(shape: Shape) -> Self {
+        match shape {
+            Shape::Array => "array",
+            Shape::Keyed => "keyed",
+            Shape::Columnar => "columnar",
+            Shape::Arrays => "arrays",
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = anyhow::Error;
+    fn from_str(shape: &str) -> Result<Self, Self::Err> {
+        match shape.to_lowercase().as_str() {
+            "array" | "objects" => Ok(Shape::Array),
+            "keyed" | "object" => Ok(Shape::Keyed),
+            "columnar" | "columns" => Ok(Shape::Columnar),
+            "arrays" | "rows" => Ok(Shape::Arrays),
+            v => anyhow::bail!("Unsupported shape: {}", v),
+        }
+    }
+}
+
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
+
+impl From<CodegenLang> for &'static str {
+    fn from(lang: CodegenLang) -> Self {
+        match lang {
+            CodegenLang::Rust => "rust",
+        }
+    }
+}
+
+impl FromStr for CodegenLang {
+    type Err = anyhow::Error;
+    fn from_str(lang: &str) -> Result<Self, Self::Err> {
+        match lang.to_lowercase().as_str() {
+            "rust" | "rs" => Ok(CodegenLang::Rust),
+            v => anyhow::bail!("Unsupported language: {}", v),
+        }
+    }
+}
+
+impl Display for CodegenLang {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Into::<&str>::into(*self))
+    }
+}
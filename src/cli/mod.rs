@@ -11,7 +11,13 @@ use std::path::{Path, PathBuf};
 
 pub use self::{
     base64::{Base64DecodeOpts, Base64EncodeOpts, Base64Format, Base64Subcommand},
-    csv::{CsvOpts, OutputFormat},
+    csv::{
+        Aggregate, ArrayMode, CodegenLang, ColumnType, CsvAggOpts, CsvCodegenOpts, CsvDecryptOpts,
+        CsvDiffOpts, CsvEncryptOpts, CsvFakeOpts, CsvFromJsonOpts, CsvFromNdjsonOpts,
+        CsvFromYamlOpts, CsvJoinOpts, CsvMaskOpts, CsvMergeOpts, CsvOpts, CsvQueryOpts,
+        CsvSchemaOpts, CsvShowOpts, CsvSplitOpts, CsvStatsOpts, CsvSubcommand, CsvToSqliteOpts,
+        CsvValidateOpts, DiffStyle, InputFormat, JoinKind, OnError, OutputFormat, Shape, ShowStyle,
+    },
     genpass::GenPassOpts,
     http::{HttpServeOpts, HttpSubcommand},
     jwt::{JwtSignOpts, JwtSubcommand, JwtVerifyOpts},
@@ -32,7 +38,7 @@ pub struct Opts {
 #[enum_dispatch(CmdExcutor)]
 pub enum SubCommand {
     #[command(name = "csv", about = "Show Csv, or convert Csv to other formats")]
-    Csv(CsvOpts),
+    Csv(Box<CsvOpts>),
     #[command(name = "genpass", about = "Generate a random password")]
     GenPass(GenPassOpts),
     #[command(subcommand, about = "Base64 encode/decode")]
@@ -8,19 +8,27 @@ mod utils;
 use anyhow::Result;
 
 pub use cli::{
-    Base64Subcommand, Cha1305Subcommand, HttpServeOpts, HttpSubcommand, JwtSignOpts, JwtSubcommand,
-    JwtVerifyOpts, Opts, SubCommand, TextSignFormat, TextSubcommand,
+    Base64Subcommand, Cha1305Subcommand, CsvSubcommand, HttpServeOpts, HttpSubcommand, JwtSignOpts,
+    JwtSubcommand, JwtVerifyOpts, Opts, SubCommand, TextSignFormat, TextSubcommand,
 };
 use enum_dispatch::enum_dispatch;
 pub use process::{
-    process_csv, process_decode, process_decrypt, process_encode, process_encrypt,
-    process_generate_decode, process_generate_encode, process_generate_key, process_genpass,
-    process_http_serve, process_text_sign, process_text_verify,
+    process_csv, process_csv_agg, process_csv_codegen, process_csv_decrypt, process_csv_diff,
+    process_csv_encrypt, process_csv_fake, process_csv_from, process_csv_join, process_csv_mask,
+    process_csv_merge, process_csv_query, process_csv_schema, process_csv_show, process_csv_split,
+    process_csv_stats, process_csv_to_sqlite, process_csv_validate, process_decode,
+    process_decrypt, process_encode, process_encrypt, process_generate_decode,
+    process_generate_encode, process_generate_key, process_genpass, process_http_serve,
+    process_text_sign, process_text_verify, AggOptions, ConvertOptions, CryptOptions, CsvDialect,
+    JoinOptions, MaskOptions, RowWindow, SplitBy, SqliteOptions, TransformRule, TypeHints,
 };
 
 use cli::{
-    Base64DecodeOpts, Base64EncodeOpts, Cha1305DecryptOpt, Cha1305EncryptOpt, CsvOpts, GenPassOpts,
-    TextKeyGenerateOpts, TextSignOpts, TextVerifyOpts,
+    Base64DecodeOpts, Base64EncodeOpts, Cha1305DecryptOpt, Cha1305EncryptOpt, CsvAggOpts,
+    CsvCodegenOpts, CsvDecryptOpts, CsvDiffOpts, CsvEncryptOpts, CsvFakeOpts, CsvFromJsonOpts,
+    CsvFromNdjsonOpts, CsvFromYamlOpts, CsvJoinOpts, CsvMaskOpts, CsvMergeOpts, CsvOpts,
+    CsvQueryOpts, CsvSchemaOpts, CsvShowOpts, CsvSplitOpts, CsvStatsOpts, CsvToSqliteOpts,
+    CsvValidateOpts, GenPassOpts, TextKeyGenerateOpts, TextSignOpts, TextVerifyOpts,
 };
 
 #[allow(async_fn_in_trait)]
@@ -1,8 +1,34 @@
+use std::io::ErrorKind;
+
 use clap::Parser;
 use rcli::{CmdExcutor, Opts};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
-    opts.cmd.execute().await
+    match opts.cmd.execute().await {
+        // the reader of a pipe such as `| head` went away, that's not a failure
+        Err(e) if is_broken_pipe(&e) => Ok(()),
+        result => result,
+    }
+}
+
+/// Looks through the whole error chain, since a broken pipe usually reaches
+/// us wrapped in a csv or json error, or under some context.
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let kind = if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            Some(e.kind())
+        } else if let Some(e) = cause.downcast_ref::<csv::Error>() {
+            match e.kind() {
+                csv::ErrorKind::Io(e) => Some(e.kind()),
+                _ => None,
+            }
+        } else if let Some(e) = cause.downcast_ref::<serde_json::Error>() {
+            e.io_error_kind()
+        } else {
+            None
+        };
+        kind == Some(ErrorKind::BrokenPipe)
+    })
 }
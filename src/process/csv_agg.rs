@@ -0,0 +1,361 @@
+use std::{cmp::Ordering, collections::HashMap, io::Read};
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::{Reader, StringRecord};
+use serde_json::{Map, Number, Value};
+
+use super::{csv_output::row_writer, CsvDialect};
+use crate::{
+    cli::{Aggregate, OutputFormat},
+    utils::get_writer,
+};
+
+/// What to group by and what to compute for every group. Each output row has
+/// the group columns, `count`, and a `fn(column)` field per aggregate.
+#[derive(Debug, Clone, Default)]
+pub struct AggOptions {
+    pub group_by: Vec<String>,
+    pub count: bool,
+    pub aggregates: Vec<(Aggregate, String)>,
+    /// The chrono format min and max read cells that aren't numbers with.
+    pub date_format: Option<String>,
+}
+
+/// What min and max order cells by: numbers, dates when a date format is
+/// given, and otherwise the text itself, as `compare` does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OrderKey {
+    Number(f64),
+    Date(NaiveDateTime),
+    Text,
+}
+
+impl OrderKey {
+    fn parse(cell: &str, date_format: Option<&str>) -> Result<Self> {
+        if let Ok(v) = cell.parse::<f64>() {
+            return Ok(OrderKey::Number(v));
+        }
+        let Some(fmt) = date_format else {
+            return Ok(OrderKey::Text);
+        };
+        if let Ok((datetime, _)) = NaiveDateTime::parse_and_remainder(cell, fmt) {
+            return Ok(OrderKey::Date(datetime));
+        }
+        match NaiveDate::parse_and_remainder(cell, fmt) {
+            Ok((date, _)) => Ok(OrderKey::Date(date.and_time(Default::default()))),
+            Err(e) => anyhow::bail!("Can't read {:?} as a date with {:?}: {}", cell, fmt, e),
+        }
+    }
+}
+
+/// Orders two parsed cells, falling back to their text when the keys are of
+/// different kinds.
+fn order((a, a_cell): &(OrderKey, String), (b, b_cell): &(OrderKey, String)) -> Ordering {
+    match (a, b) {
+        (OrderKey::Number(a), OrderKey::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (OrderKey::Date(a), OrderKey::Date(b)) => a.cmp(b),
+        _ => a_cell.cmp(b_cell),
+    }
+}
+
+/// Running state of one aggregate over one group. Integer sums are kept
+/// exactly while every cell is an integer.
+#[derive(Debug, Clone, Default)]
+struct Acc {
+    min: Option<(OrderKey, String)>,
+    max: Option<(OrderKey, String)>,
+    sum: f64,
+    int_sum: i128,
+    all_int: bool,
+    values: usize,
+}
+
+impl Acc {
+    fn new() -> Self {
+        Self {
+            all_int: true,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, agg: Aggregate, cell: &str, date_format: Option<&str>) -> Result<()> {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            return Ok(());
+        }
+        match agg {
+            Aggregate::Min => {
+                let key = (OrderKey::parse(cell, date_format)?, cell.to_string());
+                if self.min.as_ref().is_none_or(|m| order(&key, m).is_lt()) {
+                    self.min = Some(key);
+                }
+            }
+            Aggregate::Max => {
+                let key = (OrderKey::parse(cell, date_format)?, cell.to_string());
+                if self.max.as_ref().is_none_or(|m| order(&key, m).is_gt()) {
+                    self.max = Some(key);
+                }
+            }
+            Aggregate::Sum | Aggregate::Avg => {
+                let Ok(v) = cell.parse::<f64>() else {
+                    anyhow::bail!("Not a number: {}", cell);
+                };
+                match cell.parse::<i64>() {
+                    Ok(i) if self.all_int => self.int_sum += i as i128,
+                    _ => self.all_int = false,
+                }
+                self.sum += v;
+            }
+        }
+        self.values += 1;
+        Ok(())
+    }
+
+    fn value(&self, agg: Aggregate) -> Value {
+        match agg {
+            Aggregate::Min => self
+                .min
+                .as_ref()
+                .map_or(Value::Null, |(_, m)| cell_value(m)),
+            Aggregate::Max => self
+                .max
+                .as_ref()
+                .map_or(Value::Null, |(_, m)| cell_value(m)),
+            Aggregate::Sum if self.all_int => {
+                i64::try_from(self.int_sum).map_or_else(|_| float(self.int_sum as f64), Value::from)
+            }
+            Aggregate::Sum => float(self.sum),
+            Aggregate::Avg if self.values == 0 => Value::Null,
+            Aggregate::Avg if self.all_int => float(self.int_sum as f64 / self.values as f64),
+            Aggregate::Avg => float(self.sum / self.values as f64),
+        }
+    }
+}
+
+/// Orders two cells as numbers when both parse as one, otherwise as strings.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// A cell as a json number when it parses as one, otherwise as a string.
+pub fn cell_value(cell: &str) -> Value {
+    if let Ok(v) = cell.parse::<i64>() {
+        return Value::from(v);
+    }
+    match cell.parse::<f64>().ok().and_then(Number::from_f64) {
+        Some(v) => Value::Number(v),
+        None => Value::String(cell.to_string()),
+    }
+}
+
+fn float(v: f64) -> Value {
+    Number::from_f64(v).map_or(Value::Null, Value::Number)
+}
+
+/// Groups the records and computes the aggregates, with groups in the order
+/// they first appear.
+pub fn aggregate<R: Read>(
+    reader: &mut Reader<R>,
+    headers: &StringRecord,
+    opts: &AggOptions,
+) -> Result<Vec<Value>> {
+    let position = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown column: {}", name))
+    };
+    let keys = opts
+        .group_by
+        .iter()
+        .map(|name| position(name))
+        .collect::<Result<Vec<_>>>()?;
+    let columns = opts
+        .aggregates
+        .iter()
+        .map(|(agg, name)| Ok((*agg, position(name)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut index = HashMap::new();
+    let mut groups: Vec<(Vec<String>, usize, Vec<Acc>)> = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let key = keys
+            .iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect::<Vec<_>>();
+        let idx = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, 0, vec![Acc::new(); columns.len()]));
+            groups.len() - 1
+        });
+        let (_, count, accs) = &mut groups[idx];
+        *count += 1;
+        for ((agg, col), acc) in columns.iter().zip(accs.iter_mut()) {
+            let cell = record.get(*col).unwrap_or("");
+            acc.add(*agg, cell, opts.date_format.as_deref())
+                .map_err(|e| {
+                    let line = record.position().map_or(0, |p| p.line());
+                    anyhow::anyhow!("Line {}, column {:?}: {}", line, &headers[*col], e)
+                })?;
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .map(|(key, count, accs)| {
+            let mut row = opts
+                .group_by
+                .iter()
+                .cloned()
+                .zip(key.into_iter().map(Value::String))
+                .collect::<Map<_, _>>();
+            if opts.count || opts.aggregates.is_empty() {
+                row.insert("count".to_string(), Value::from(count));
+            }
+            for ((agg, name), acc) in opts.aggregates.iter().zip(accs) {
+                row.insert(format!("{}({})", agg, name), acc.value(*agg));
+            }
+            Value::Object(row)
+        })
+        .collect())
+}
+
+pub fn process_csv_agg(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    toml_key: &str,
+    dialect: &CsvDialect,
+    opts: &AggOptions,
+) -> Result<()> {
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    let rows = aggregate(&mut reader, &headers, opts)?;
+    let mut writer = row_writer(format, get_writer(output)?, toml_key);
+    for row in rows {
+        writer.write_row(row)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_agg_juventus_by_position() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let headers = dialect.headers(&mut reader)?;
+        let opts = AggOptions {
+            group_by: vec!["Position".to_string()],
+            count: true,
+            aggregates: vec![
+                (Aggregate::Min, "Kit Number".to_string()),
+                (Aggregate::Max, "Name".to_string()),
+            ],
+            ..Default::default()
+        };
+        let rows = aggregate(&mut reader, &headers, &opts)?;
+        assert_eq!(rows[0]["Position"], json!("Goalkeeper"));
+        assert_eq!(rows[0]["min(Kit Number)"], json!(1));
+        assert_eq!(rows[0]["max(Name)"], json!("Wojciech Szczesny"));
+        let total: u64 = rows.iter().filter_map(|r| r["count"].as_u64()).sum();
+        assert_eq!(total, 27);
+        Ok(())
+    }
+
+    #[test]
+    fn test_agg_sum_avg() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let data = "team,goals,rating\na,2,6.5\nb,1,\na,3,7.5\n";
+        let mut reader = dialect.reader(data.as_bytes());
+        let headers = dialect.headers(&mut reader)?;
+        let opts = AggOptions {
+            group_by: vec!["team".to_string()],
+            count: false,
+            aggregates: vec![
+                (Aggregate::Sum, "goals".to_string()),
+                (Aggregate::Avg, "rating".to_string()),
+            ],
+            ..Default::default()
+        };
+        let rows = aggregate(&mut reader, &headers, &opts)?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"team": "a", "sum(goals)": 5, "avg(rating)": 7.0}),
+                json!({"team": "b", "sum(goals)": 1, "avg(rating)": null}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_agg_dates_and_big_sums() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let headers = dialect.headers(&mut reader)?;
+        let opts = AggOptions {
+            group_by: vec!["Nationality".to_string()],
+            aggregates: vec![
+                (Aggregate::Min, "DOB".to_string()),
+                (Aggregate::Max, "DOB".to_string()),
+            ],
+            date_format: Some("%b %d, %Y".to_string()),
+            ..Default::default()
+        };
+        let rows = aggregate(&mut reader, &headers, &opts)?;
+        let italy = rows
+            .iter()
+            .find(|r| r["Nationality"] == "Italy")
+            .ok_or_else(|| anyhow::anyhow!("no Italy group"))?;
+        assert_eq!(italy["min(DOB)"], json!("Jan 28, 1978 (41)"));
+
+        // without a format, dates are ordered as text
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let opts = AggOptions {
+            date_format: None,
+            ..opts
+        };
+        let rows = aggregate(&mut reader, &headers, &opts)?;
+        let italy = rows
+            .iter()
+            .find(|r| r["Nationality"] == "Italy")
+            .ok_or_else(|| anyhow::anyhow!("no Italy group"))?;
+        assert_eq!(italy["min(DOB)"], json!("Aug 14, 1984 (35)"));
+
+        let data = "n\n9007199254740993\n1\n";
+        let mut reader = dialect.reader(data.as_bytes());
+        let headers = dialect.headers(&mut reader)?;
+        let opts = AggOptions {
+            aggregates: vec![(Aggregate::Sum, "n".to_string())],
+            ..Default::default()
+        };
+        let rows = aggregate(&mut reader, &headers, &opts)?;
+        assert_eq!(rows[0]["sum(n)"], json!(9007199254740994_i64));
+        Ok(())
+    }
+
+    #[test]
+    fn test_agg_errors() {
+        let dialect = CsvDialect::default();
+        let headers = StringRecord::from(vec!["team", "goals"]);
+        let mut reader = dialect.reader(&b"team,goals\na,x\n"[..]);
+        let opts = AggOptions {
+            aggregates: vec![(Aggregate::Avg, "goals".to_string())],
+            ..Default::default()
+        };
+        assert!(aggregate(&mut reader, &headers, &opts).is_err());
+        let opts = AggOptions {
+            group_by: vec!["club".to_string()],
+            ..Default::default()
+        };
+        assert!(aggregate(&mut reader, &headers, &opts).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use csv::StringRecord;
+
+use super::{
+    csv_infer::{infer_cell, merge_type},
+    CsvDialect,
+};
+use crate::cli::{CodegenLang, ColumnType};
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// What a column holds once every row has been seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Column {
+    ty: Option<ColumnType>,
+    /// Some cell was empty.
+    optional: bool,
+    /// Every bool cell is spelled the way serde parses it.
+    plain_bools: bool,
+}
+
+impl Column {
+    fn rust_type(&self) -> &'static str {
+        match (self.ty, self.optional) {
+            (Some(ColumnType::Int), false) => "i64",
+            (Some(ColumnType::Int), true) => "Option<i64>",
+            (Some(ColumnType::Float), false) => "f64",
+            (Some(ColumnType::Float), true) => "Option<f64>",
+            (Some(ColumnType::Bool), false) if self.plain_bools => "bool",
+            (Some(ColumnType::Bool), true) if self.plain_bools => "Option<bool>",
+            // an empty cell is just an empty string
+            _ => "String",
+        }
+    }
+}
+
+/// Splits a header into lowercase words joined by `_`: `Kit Number` becomes
+/// `kit_number`, `firstName` becomes `first_name`, `HTTPCode` becomes
+/// `http_code`. Anything that isn't an ascii letter or digit separates words.
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            let boundary = prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_lower);
+            if boundary && !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+/// The field name serde sees for a snake_case field under `rename_all`.
+fn serde_case(field: &str, rule: &str) -> String {
+    match rule {
+        "PascalCase" | "camelCase" => {
+            let mut out = field
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars.next().map_or(String::new(), |first| {
+                        first.to_ascii_uppercase().to_string() + chars.as_str()
+                    })
+                })
+                .collect::<String>();
+            if rule == "camelCase" {
+                if let Some(first) = out.get_mut(0..1) {
+                    first.make_ascii_lowercase();
+                }
+            }
+            out
+        }
+        _ => field.to_string(),
+    }
+}
+
+/// Rust field names for the headers, unique. Keywords are left for the
+/// caller to write as raw identifiers, except the few that can't be one.
+fn field_names(headers: &StringRecord) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(headers.len());
+    for header in headers {
+        let mut name = snake_case(header);
+        if name.is_empty() {
+            name = "field".to_string();
+        } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+            name = format!("field_{}", name);
+        } else if matches!(name.as_str(), "self" | "super" | "crate") {
+            name.push('_');
+        }
+        let base = name.clone();
+        let mut n = 1;
+        while names.contains(&name) {
+            n += 1;
+            name = format!("{}_{}", base, n);
+        }
+        names.push(name);
+    }
+    names
+}
+
+fn struct_name(name: &str) -> String {
+    match serde_case(&snake_case(name), "PascalCase") {
+        name if name.is_empty() => "Record".to_string(),
+        name if name.starts_with(|c: char| c.is_ascii_digit()) => format!("Record{}", name),
+        name => name,
+    }
+}
+
+/// A serde struct for the rows, fields in header order. `rename_all` is
+/// picked to leave as few per-field renames as it can.
+fn rust_struct(name: &str, headers: &StringRecord, columns: &[Column]) -> Result<String> {
+    let fields = field_names(headers);
+    let renames = |rule: &str| {
+        fields
+            .iter()
+            .zip(headers.iter())
+            .filter(|(field, header)| serde_case(field, rule) != *header)
+            .count()
+    };
+    let rule = ["", "PascalCase", "camelCase"]
+        .into_iter()
+        .min_by_key(|rule| renames(rule))
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    writeln!(out, "use serde::{{Deserialize, Serialize}};")?;
+    writeln!(out)?;
+    writeln!(out, "#[derive(Debug, Clone, Deserialize, Serialize)]")?;
+    if !rule.is_empty() {
+        writeln!(out, "#[serde(rename_all = {:?})]", rule)?;
+    }
+    writeln!(out, "pub struct {} {{", struct_name(name))?;
+    for ((field, header), column) in fields.iter().zip(headers.iter()).zip(columns) {
+        if serde_case(field, rule) != header {
+            writeln!(out, "    #[serde(rename = {:?})]", header)?;
+        }
+        let ident = if RUST_KEYWORDS.contains(&field.as_str()) {
+            format!("r#{}", field)
+        } else {
+            field.clone()
+        };
+        writeln!(out, "    pub {}: {},", ident, column.rust_type())?;
+    }
+    writeln!(out, "}}")?;
+    Ok(out)
+}
+
+/// Generates a type for the rows of `input`, named `name`, with field types
+/// inferred from every row.
+pub fn process_csv_codegen(
+    input: &str,
+    dialect: &CsvDialect,
+    lang: CodegenLang,
+    name: &str,
+) -> Result<String> {
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    let mut columns = vec![
+        Column {
+            ty: None,
+            optional: false,
+            plain_bools: true,
+        };
+        headers.len()
+    ];
+    for record in reader.records() {
+        let record = record?;
+        for (column, cell) in columns.iter_mut().zip(record.iter()) {
+            let ty = infer_cell(cell);
+            column.ty = merge_type(column.ty, ty);
+            column.optional |= ty.is_none();
+            if ty == Some(ColumnType::Bool) {
+                column.plain_bools &= matches!(cell, "true" | "false");
+            }
+        }
+    }
+    match lang {
+        CodegenLang::Rust => rust_struct(name, &headers, &columns),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_juventus_struct() -> Result<()> {
+        let code = process_csv_codegen(
+            "assets/juventus.csv",
+            &CsvDialect::default(),
+            CodegenLang::Rust,
+            "player",
+        )?;
+        assert_eq!(
+            code,
+            "use serde::{Deserialize, Serialize};\n\
+             \n\
+             #[derive(Debug, Clone, Deserialize, Serialize)]\n\
+             #[serde(rename_all = \"PascalCase\")]\n\
+             pub struct Player {\n    \
+                 pub name: String,\n    \
+                 pub position: String,\n    \
+                 #[serde(rename = \"DOB\")]\n    \
+                 pub dob: String,\n    \
+                 pub nationality: String,\n    \
+                 #[serde(rename = \"Kit Number\")]\n    \
+                 pub kit_number: i64,\n\
+             }\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_names() {
+        let headers = StringRecord::from(vec![
+            "firstName",
+            "HTTPCode",
+            "type",
+            "2nd place",
+            "",
+            "First Name",
+            "e-mail (work)",
+        ]);
+        assert_eq!(
+            field_names(&headers),
+            [
+                "first_name",
+                "http_code",
+                "type",
+                "field_2nd_place",
+                "field",
+                "first_name_2",
+                "e_mail_work"
+            ]
+        );
+        assert_eq!(struct_name("juventus-2019"), "Juventus2019");
+        let columns = [Column {
+            ty: Some(ColumnType::Int),
+            optional: true,
+            plain_bools: true,
+        }];
+        let code = rust_struct("x", &StringRecord::from(vec!["type"]), &columns)
+            .expect("writing to a string");
+        assert!(code.contains("    pub r#type: Option<i64>,\n"));
+        assert!(!code.contains("rename"));
+    }
+}
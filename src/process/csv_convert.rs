@@ -1,41 +1,263 @@
-use std::fs;
+use std::io::Read;
 
-use csv::Reader;
-use serde::{Deserialize, Serialize};
+use csv::StringRecord;
 use serde_json::Value;
 
-use crate::cli::OutputFormat;
+use super::{
+    csv_expr::Expr,
+    csv_infer::typed_row,
+    csv_nested::{is_nested, unflatten},
+    csv_output::shaped_writer,
+    csv_reject::Rejects,
+    csv_select::Projection,
+    csv_transform::Transforms,
+    CsvDialect, TransformRule, TypeHints,
+};
+use crate::{
+    cli::{OnError, OutputFormat, Shape},
+    utils::{get_vec, get_writer},
+};
 
-// use crate::cli::OutputFormat;
+/// Everything that shapes a conversion besides where the data comes from and
+/// goes to. Column names refer to the input header, plus any columns the
+/// transforms add; transforms run before everything else.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub types: TypeHints,
+    pub toml_key: String,
+    pub select: Vec<String>,
+    pub rename: Vec<(String, String)>,
+    pub filter: Option<String>,
+    pub nested: bool,
+    pub transforms: Vec<TransformRule>,
+    pub shape: Shape,
+    /// The output column `Shape::Keyed` indexes rows by.
+    pub key_by: Option<String>,
+    pub on_error: OnError,
+    /// Where `OnError::Report` writes the rejected rows.
+    pub rejects: String,
+}
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct Player {
-    name: String,
-    position: String,
-    #[serde(rename = "DOB")]
-    dob: String,
-    nationality: String,
-    #[serde(rename = "Kit Number")]
-    kit: u8,
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            types: TypeHints::default(),
+            toml_key: "rows".to_string(),
+            select: Vec::new(),
+            rename: Vec::new(),
+            filter: None,
+            nested: false,
+            transforms: Vec::new(),
+            shape: Shape::Array,
+            key_by: None,
+            on_error: OnError::Fail,
+            rejects: "rejected.csv".to_string(),
+        }
+    }
 }
 
-pub fn process_csv(input: &str, output: String, format: OutputFormat) -> anyhow::Result<()> {
-    let mut reader = Reader::from_path(input)?;
-    let mut ret = Vec::with_capacity(128);
-    let headers = reader.headers()?.clone();
-    for result in reader.records() {
-        let record = result?;
-        let json_value = headers.iter().zip(record.iter()).collect::<Value>();
-        ret.push(json_value);
+pub fn process_csv(
+    input: &str,
+    output: &str,
+    format: OutputFormat,
+    dialect: &CsvDialect,
+    opts: &ConvertOptions,
+) -> anyhow::Result<()> {
+    if opts.nested && matches!(opts.shape, Shape::Columnar | Shape::Arrays) {
+        anyhow::bail!("--nested only works with --shape array or keyed");
+    }
+    // rows are keyed after they are nested, when a dotted key is gone
+    if let Some(key) = opts.key_by.as_deref().filter(|key| is_nested(key)) {
+        if opts.nested && matches!(opts.shape, Shape::Keyed) {
+            anyhow::bail!("--key-by can't be a nested column with --nested: {}", key);
+        }
     }
+    // stdin can only be read once, so hold on to it when inference needs a
+    // second pass over the records
+    let buffered = if input == "-" && opts.types.infer {
+        Some(get_vec(input)?)
+    } else {
+        None
+    };
+    let open = |dialect: &CsvDialect| match &buffered {
+        Some(data) => dialect.decoded(Box::new(data.as_slice()) as Box<dyn Read>),
+        None => dialect.open(input),
+    };
+    let tolerant = opts.on_error != OnError::Fail;
+    // when bad rows are skipped or reported, rows with the wrong number of
+    // fields are caught below rather than by the csv reader, which would
+    // drop what they held
+    let lenient = CsvDialect {
+        flexible: dialect.flexible || tolerant,
+        ..dialect.clone()
+    };
+    let mut reader = open(&lenient)?;
+    let raw_headers = dialect.headers(&mut reader)?;
+    let transforms = Transforms::new(&raw_headers, &opts.transforms)?;
+    let headers = transforms.headers();
+    // bad rows are left out of inference too, unless they fail the run
+    let types = opts.types.resolve_records(headers, || {
+        Ok(open(dialect)?
+            .into_records()
+            .map(|record| transforms.apply(record?))
+            .filter(|record| !tolerant || record.is_ok()))
+    })?;
+    let projection = Projection::new(headers, &opts.select, &opts.rename)?;
+    let filter = opts
+        .filter
+        .as_deref()
+        .map(|src| Expr::parse(src, headers))
+        .transpose()?;
 
-    let content = match format {
-        OutputFormat::Json => serde_json::to_string_pretty(&ret)?,
-        OutputFormat::Yaml => serde_yaml::to_string(&ret)?,
-        // OutputFormat::Toml => toml::to_string(&ret)?,
+    let to_value = |record: StringRecord| -> anyhow::Result<Option<Value>> {
+        let record = transforms.apply(record)?;
+        if filter.as_ref().is_some_and(|f| !f.matches(&record)) {
+            return Ok(None);
+        }
+        let row = typed_row(&projection, &record, &types)?;
+        Ok(Some(if opts.nested {
+            unflatten(row)?
+        } else {
+            Value::Object(row)
+        }))
     };
 
-    fs::write(output, content)?;
-    Ok(())
+    let columns = projection
+        .iter()
+        .map(|(_, name)| name.to_string())
+        .collect();
+    let mut writer = shaped_writer(
+        opts.shape,
+        format,
+        get_writer(output)?,
+        &opts.toml_key,
+        columns,
+        opts.key_by.as_deref(),
+    )?;
+    let mut rejects = Rejects::new(opts.on_error, &opts.rejects, dialect.delimiter)?;
+    for result in reader.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) if e.is_io_error() => return Err(e.into()),
+            Err(e) => {
+                let position = e.position().cloned();
+                rejects.reject(e.into(), position.as_ref(), None)?;
+                continue;
+            }
+        };
+        if !dialect.flexible && record.len() != raw_headers.len() {
+            let err = anyhow::anyhow!(
+                "Expected {} fields, found {}",
+                raw_headers.len(),
+                record.len()
+            );
+            rejects.reject(err, record.position(), Some(&record))?;
+            continue;
+        }
+        let position = record.position().cloned();
+        let original = (opts.on_error == OnError::Report).then(|| record.clone());
+        match to_value(record) {
+            Ok(Some(value)) => writer.write_row(value)?,
+            Ok(None) => {}
+            Err(e) => rejects.reject(e, position.as_ref(), original.as_ref())?,
+        }
+    }
+    writer.finish()?;
+    rejects.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_keeps_bad_rows() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-convert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        std::fs::write(path("in.csv"), "a,b\n1,2\n3\n4,5,6\n7,8\n")?;
+        let opts = ConvertOptions {
+            on_error: OnError::Report,
+            rejects: path("rejected.csv"),
+            ..Default::default()
+        };
+        let dialect = CsvDialect::default();
+        process_csv(
+            &path("in.csv"),
+            &path("out.json"),
+            OutputFormat::Json,
+            &dialect,
+            &opts,
+        )?;
+        let rows: Vec<Value> = serde_json::from_str(&std::fs::read_to_string(path("out.json"))?)?;
+        let report = std::fs::read_to_string(path("rejected.csv"))?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            report.lines().collect::<Vec<_>>(),
+            [
+                "line,byte,error,record",
+                "3,8,\"Expected 2 fields, found 1\",3",
+                "4,10,\"Expected 2 fields, found 3\",\"4,5,6\"",
+            ]
+        );
+
+        // a bad --shape combination fails before the report file is created
+        let opts = ConvertOptions {
+            nested: true,
+            shape: Shape::Columnar,
+            rejects: path("never.csv"),
+            ..opts
+        };
+        assert!(process_csv(
+            "assets/juventus.csv",
+            "-",
+            OutputFormat::Json,
+            &dialect,
+            &opts
+        )
+        .is_err());
+        let created = dir.join("never.csv").exists();
+        std::fs::remove_dir_all(&dir)?;
+        assert!(!created);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_key_by() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("rcli-key-by-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        std::fs::write(
+            path("in.csv"),
+            "id,player.name
+1,Buffon
+",
+        )?;
+        let opts = |key: &str| ConvertOptions {
+            nested: true,
+            shape: Shape::Keyed,
+            key_by: Some(key.to_string()),
+            ..Default::default()
+        };
+        let run = |key: &str| {
+            let dialect = CsvDialect::default();
+            process_csv(
+                &path("in.csv"),
+                &path("out.json"),
+                OutputFormat::Json,
+                &dialect,
+                &opts(key),
+            )
+        };
+        let nested = run("player.name");
+        let flat = run("id").and_then(|_| Ok(std::fs::read_to_string(path("out.json"))?));
+        std::fs::remove_dir_all(&dir)?;
+        assert!(nested.is_err());
+        let doc: Value = serde_json::from_str(&flat?)?;
+        assert_eq!(
+            doc,
+            serde_json::json!({"1": {"id": "1", "player": {"name": "Buffon"}}})
+        );
+        Ok(())
+    }
 }
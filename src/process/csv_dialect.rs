@@ -0,0 +1,250 @@
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::Result;
+use csv::{Reader, ReaderBuilder, StringRecord};
+use encoding_rs::Encoding;
+
+use super::csv_encoding::{decode_reader, detect};
+use crate::utils::get_reader;
+
+const SNIFF_SAMPLE_SIZE: u64 = 64 * 1024;
+const SNIFF_DELIMITERS: &[u8] = b",;\t|:";
+const SNIFF_QUOTES: &[u8] = b"\"'";
+
+/// How a csv file is laid out: its encoding, separators, quoting, comments
+/// and whether the first record is a header. Without an `encoding` it is
+/// detected when the input is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvDialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub escape: Option<u8>,
+    pub comment: Option<u8>,
+    pub flexible: bool,
+    pub has_headers: bool,
+    pub encoding: Option<&'static Encoding>,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            escape: None,
+            comment: None,
+            flexible: false,
+            has_headers: true,
+            encoding: None,
+        }
+    }
+}
+
+impl CsvDialect {
+    pub fn builder(&self) -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .escape(self.escape)
+            .comment(self.comment)
+            .flexible(self.flexible)
+            .has_headers(self.has_headers);
+        builder
+    }
+
+    pub fn reader<R: Read>(&self, rdr: R) -> Reader<R> {
+        self.builder().from_reader(rdr)
+    }
+
+    /// Opens a file, or stdin for `-`, transcoded to UTF-8.
+    pub fn open(&self, input: &str) -> Result<Reader<Box<dyn Read>>> {
+        self.decoded(get_reader(input)?)
+    }
+
+    /// Like `reader`, but transcodes the input to UTF-8 first.
+    pub fn decoded<'a, R: Read + 'a>(&self, rdr: R) -> Result<Reader<Box<dyn Read + 'a>>> {
+        Ok(self.reader(decode_reader(rdr, self.encoding)?))
+    }
+
+    pub fn reader_from_path<P: AsRef<Path>>(&self, path: P) -> Result<Reader<File>> {
+        Ok(self.builder().from_path(path)?)
+    }
+
+    /// Returns the column names, naming them `col1..colN` when the file has
+    /// no header row.
+    pub fn headers<R: Read>(&self, reader: &mut Reader<R>) -> Result<StringRecord> {
+        let headers = reader.headers()?;
+        if self.has_headers {
+            return Ok(headers.clone());
+        }
+        Ok((1..=headers.len()).map(|i| format!("col{}", i)).collect())
+    }
+
+    /// Guesses the dialect of a file from its first few kilobytes, along
+    /// with its encoding unless one is given. Stdin can't be rewound after
+    /// sampling, so it can't be sniffed.
+    pub fn sniff_path<P: AsRef<Path>>(
+        path: P,
+        encoding: Option<&'static Encoding>,
+    ) -> Result<Self> {
+        if path.as_ref() == Path::new("-") {
+            anyhow::bail!("Can't sniff stdin, give the dialect options instead");
+        }
+        let mut raw = Vec::new();
+        File::open(path)?
+            .take(SNIFF_SAMPLE_SIZE)
+            .read_to_end(&mut raw)?;
+        let encoding = encoding.unwrap_or_else(|| detect(&raw).encoding);
+        let mut sample = Vec::new();
+        decode_reader(raw.as_slice(), Some(encoding))?.read_to_end(&mut sample)?;
+        Ok(Self {
+            encoding: Some(encoding),
+            ..Self::sniff(&sample)
+        })
+    }
+
+    /// Guesses the dialect from a sample by trying every candidate delimiter
+    /// and quote, and keeping the one that splits rows most consistently.
+    pub fn sniff(sample: &[u8]) -> Self {
+        // drop a trailing partial line so a truncated sample doesn't skew counts
+        let sample = match sample.iter().rposition(|&b| b == b'\n') {
+            Some(pos) if pos + 1 < sample.len() => &sample[..=pos],
+            _ => sample,
+        };
+        let comment = sample
+            .split(|&b| b == b'\n')
+            .any(|line| line.starts_with(b"#"))
+            .then_some(b'#');
+
+        let mut best = Self {
+            comment,
+            ..Default::default()
+        };
+        let mut best_score = (0.0, 0);
+        for &quote in SNIFF_QUOTES {
+            for &delimiter in SNIFF_DELIMITERS {
+                let candidate = Self {
+                    delimiter,
+                    quote,
+                    comment,
+                    ..Default::default()
+                };
+                let (consistency, fields) = candidate.score(sample);
+                if fields > 1 && (consistency, fields) > best_score {
+                    best_score = (consistency, fields);
+                    best = Self {
+                        flexible: consistency < 1.0,
+                        ..candidate
+                    };
+                }
+            }
+        }
+        best.has_headers = best.sniff_headers(sample);
+        best
+    }
+
+    /// Returns the share of rows that have the most common field count, and
+    /// that field count.
+    fn score(&self, sample: &[u8]) -> (f64, usize) {
+        let mut reader = Self {
+            flexible: true,
+            has_headers: false,
+            ..self.clone()
+        }
+        .reader(sample);
+        let mut counts: Vec<(usize, usize)> = Vec::new();
+        let mut total = 0;
+        for record in reader.records() {
+            let Ok(record) = record else {
+                return (0.0, 0);
+            };
+            total += 1;
+            match counts.iter_mut().find(|(len, _)| *len == record.len()) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((record.len(), 1)),
+            }
+        }
+        match counts.into_iter().max_by_key(|&(len, n)| (n, len)) {
+            Some((len, n)) => (n as f64 / total as f64, len),
+            None => (0.0, 0),
+        }
+    }
+
+    /// A first row with numbers in it is data; otherwise assume it's a header.
+    fn sniff_headers(&self, sample: &[u8]) -> bool {
+        let mut reader = Self {
+            flexible: true,
+            has_headers: false,
+            ..self.clone()
+        }
+        .reader(sample);
+        match reader.records().next() {
+            Some(Ok(first)) => !first.iter().any(|cell| cell.trim().parse::<f64>().is_ok()),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_semicolon() {
+        let sample = b"name;age;city\nalice;30;\"Paris; France\"\nbob;25;Berlin\n";
+        let dialect = CsvDialect::sniff(sample);
+        assert_eq!(dialect.delimiter, b';');
+        assert_eq!(dialect.quote, b'"');
+        assert!(dialect.has_headers);
+        assert!(!dialect.flexible);
+    }
+
+    #[test]
+    fn test_sniff_headerless_tabs() {
+        let sample = b"alice\t30\tParis\nbob\t25\tBerlin\n";
+        let dialect = CsvDialect::sniff(sample);
+        assert_eq!(dialect.delimiter, b'\t');
+        assert!(!dialect.has_headers);
+    }
+
+    #[test]
+    fn test_sniff_juventus() -> Result<()> {
+        let dialect = CsvDialect::sniff_path("assets/juventus.csv", None)?;
+        assert_eq!(
+            dialect,
+            CsvDialect {
+                encoding: Some(encoding_rs::UTF_8),
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_headerless_names() -> Result<()> {
+        let dialect = CsvDialect {
+            has_headers: false,
+            ..Default::default()
+        };
+        let mut reader = dialect.reader(&b"a,b,c\nd,e,f\n"[..]);
+        let headers = dialect.headers(&mut reader)?;
+        assert_eq!(headers, vec!["col1", "col2", "col3"]);
+        assert_eq!(reader.records().count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_comment_and_escape() -> Result<()> {
+        let dialect = CsvDialect {
+            delimiter: b'|',
+            escape: Some(b'\\'),
+            comment: Some(b'#'),
+            ..Default::default()
+        };
+        let mut reader = dialect.reader(&b"# exported\nk|v\na|\"say \\\"hi\\\"\"\n"[..]);
+        assert_eq!(dialect.headers(&mut reader)?, vec!["k", "v"]);
+        let record = reader.records().next().expect("one record")?;
+        assert_eq!(&record[1], "say \"hi\"");
+        Ok(())
+    }
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord, WriterBuilder};
+use serde_json::{json, Map, Value};
+
+use super::CsvDialect;
+use crate::{cli::DiffStyle, utils::get_writer};
+
+/// Row number by key.
+type KeyIndex = HashMap<Vec<String>, usize>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowChange {
+    Added(StringRecord),
+    Removed(StringRecord),
+    /// Both versions of the row, and the columns whose cells differ.
+    Changed {
+        old: StringRecord,
+        new: StringRecord,
+        columns: Vec<String>,
+    },
+}
+
+/// Row differences between two versions of a file, matched up by key.
+/// Cells are only compared in columns both versions have.
+#[derive(Debug, Clone)]
+pub struct CsvDiff {
+    pub old_headers: StringRecord,
+    pub new_headers: StringRecord,
+    pub key: Vec<String>,
+    pub changes: Vec<RowChange>,
+}
+
+/// A cell by column name, `""` when the file has no such column.
+fn cell<'a>(headers: &StringRecord, record: &'a StringRecord, name: &str) -> &'a str {
+    headers
+        .iter()
+        .position(|h| h == name)
+        .and_then(|i| record.get(i))
+        .unwrap_or("")
+}
+
+impl CsvDiff {
+    pub fn added_columns(&self) -> Vec<&str> {
+        self.new_headers
+            .iter()
+            .filter(|h| !self.old_headers.iter().any(|o| o == *h))
+            .collect()
+    }
+
+    pub fn removed_columns(&self) -> Vec<&str> {
+        self.old_headers
+            .iter()
+            .filter(|h| !self.new_headers.iter().any(|n| n == *h))
+            .collect()
+    }
+
+    fn key_of(&self, change: &RowChange) -> Vec<(String, String)> {
+        let (headers, record) = match change {
+            RowChange::Removed(old) => (&self.old_headers, old),
+            RowChange::Added(new) | RowChange::Changed { new, .. } => (&self.new_headers, new),
+        };
+        self.key
+            .iter()
+            .map(|k| (k.clone(), cell(headers, record, k).to_string()))
+            .collect()
+    }
+
+    fn row_json(headers: &StringRecord, record: &StringRecord) -> Value {
+        Value::Object(
+            headers
+                .iter()
+                .zip(record.iter())
+                .map(|(h, c)| (h.to_string(), Value::String(c.to_string())))
+                .collect(),
+        )
+    }
+
+    pub fn to_human(&self) -> String {
+        let mut out = String::new();
+        for name in self.added_columns() {
+            out.push_str(&format!("+ column {}\n", name));
+        }
+        for name in self.removed_columns() {
+            out.push_str(&format!("- column {}\n", name));
+        }
+        for change in &self.changes {
+            let key = self
+                .key_of(change)
+                .into_iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join(", ");
+            match change {
+                RowChange::Added(_) => out.push_str(&format!("+ {}\n", key)),
+                RowChange::Removed(_) => out.push_str(&format!("- {}\n", key)),
+                RowChange::Changed { old, new, columns } => {
+                    out.push_str(&format!("~ {}\n", key));
+                    for name in columns {
+                        out.push_str(&format!(
+                            "    {}: {:?} -> {:?}\n",
+                            name,
+                            cell(&self.old_headers, old, name),
+                            cell(&self.new_headers, new, name)
+                        ));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        let mut changed = Vec::new();
+        for change in &self.changes {
+            match change {
+                RowChange::Added(new) => added.push(Self::row_json(&self.new_headers, new)),
+                RowChange::Removed(old) => removed.push(Self::row_json(&self.old_headers, old)),
+                RowChange::Changed { old, new, columns } => {
+                    let key = self
+                        .key_of(change)
+                        .into_iter()
+                        .map(|(k, v)| (k, Value::String(v)))
+                        .collect::<Map<_, _>>();
+                    let cells = columns
+                        .iter()
+                        .map(|name| {
+                            let before = cell(&self.old_headers, old, name);
+                            let after = cell(&self.new_headers, new, name);
+                            (name.clone(), json!({"before": before, "after": after}))
+                        })
+                        .collect::<Map<_, _>>();
+                    changed.push(json!({"key": key, "cells": cells}));
+                }
+            }
+        }
+        json!({
+            "columns_added": self.added_columns(),
+            "columns_removed": self.removed_columns(),
+            "added": added,
+            "removed": removed,
+            "changed": changed,
+        })
+    }
+
+    /// One csv row per changed row: an `op` column (`add`, `remove` or
+    /// `change`) followed by the row itself, new values for adds and changes
+    /// and old values for removes.
+    pub fn write_patch<W: Write>(&self, out: W, delimiter: u8) -> Result<()> {
+        let mut columns = self.new_headers.iter().collect::<Vec<_>>();
+        columns.extend(self.removed_columns());
+        let mut writer = WriterBuilder::new().delimiter(delimiter).from_writer(out);
+        writer.write_record(std::iter::once("op").chain(columns.iter().copied()))?;
+        for change in &self.changes {
+            let (op, headers, record) = match change {
+                RowChange::Added(new) => ("add", &self.new_headers, new),
+                RowChange::Removed(old) => ("remove", &self.old_headers, old),
+                RowChange::Changed { new, .. } => ("change", &self.new_headers, new),
+            };
+            writer.write_record(
+                std::iter::once(op).chain(columns.iter().map(|name| cell(headers, record, name))),
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn index_rows<R: Read>(
+    reader: &mut Reader<R>,
+    keys: &[usize],
+    input: &str,
+) -> Result<(Vec<StringRecord>, KeyIndex)> {
+    let mut rows = Vec::new();
+    let mut index = HashMap::new();
+    for record in reader.records() {
+        let record = record?;
+        let key = keys
+            .iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect::<Vec<_>>();
+        if index.insert(key.clone(), rows.len()).is_some() {
+            anyhow::bail!("Duplicate key {:?} in {}", key, input);
+        }
+        rows.push(record);
+    }
+    Ok((rows, index))
+}
+
+/// Compares two readers row by row. Removed and changed rows come in the old
+/// file's order, then added rows in the new file's order.
+pub fn diff<O: Read, N: Read>(
+    old: &mut Reader<O>,
+    old_headers: StringRecord,
+    new: &mut Reader<N>,
+    new_headers: StringRecord,
+    key: &[String],
+) -> Result<CsvDiff> {
+    let position = |headers: &StringRecord, name: &str, input: &str| {
+        headers
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| anyhow::anyhow!("Key column {} missing in {} file", name, input))
+    };
+    let old_keys = key
+        .iter()
+        .map(|k| position(&old_headers, k, "old"))
+        .collect::<Result<Vec<_>>>()?;
+    let new_keys = key
+        .iter()
+        .map(|k| position(&new_headers, k, "new"))
+        .collect::<Result<Vec<_>>>()?;
+    let (old_rows, old_index) = index_rows(old, &old_keys, "old file")?;
+    let (new_rows, new_index) = index_rows(new, &new_keys, "new file")?;
+    let shared = new_headers
+        .iter()
+        .filter(|h| old_headers.iter().any(|o| o == *h))
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut changes = Vec::new();
+    let mut added = Vec::new();
+    for (key, &i) in &new_index {
+        if !old_index.contains_key(key) {
+            added.push(i);
+        }
+    }
+    for record in &old_rows {
+        let key = old_keys
+            .iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect::<Vec<_>>();
+        let Some(&i) = new_index.get(&key) else {
+            changes.push(RowChange::Removed(record.clone()));
+            continue;
+        };
+        let new_record = &new_rows[i];
+        let columns = shared
+            .iter()
+            .filter(|name| cell(&old_headers, record, name) != cell(&new_headers, new_record, name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !columns.is_empty() {
+            changes.push(RowChange::Changed {
+                old: record.clone(),
+                new: new_record.clone(),
+                columns,
+            });
+        }
+    }
+    added.sort_unstable();
+    changes.extend(
+        added
+            .into_iter()
+            .map(|i| RowChange::Added(new_rows[i].clone())),
+    );
+    Ok(CsvDiff {
+        old_headers,
+        new_headers,
+        key: key.to_vec(),
+        changes,
+    })
+}
+
+pub fn process_csv_diff(
+    old: &str,
+    new: &str,
+    key: &[String],
+    output: &str,
+    style: DiffStyle,
+    [dialect, new_dialect]: [&CsvDialect; 2],
+) -> Result<()> {
+    let mut old_reader = dialect.open(old)?;
+    let old_headers = dialect.headers(&mut old_reader)?;
+    let mut new_reader = new_dialect.open(new)?;
+    let new_headers = new_dialect.headers(&mut new_reader)?;
+    let diff = diff(
+        &mut old_reader,
+        old_headers,
+        &mut new_reader,
+        new_headers,
+        key,
+    )?;
+
+    let mut writer = get_writer(output)?;
+    match style {
+        DiffStyle::Human => writer.write_all(diff.to_human().as_bytes())?,
+        DiffStyle::Json => {
+            serde_json::to_writer_pretty(&mut writer, &diff.to_json())?;
+            writer.write_all(b"\n")?;
+        }
+        DiffStyle::Patch => diff.write_patch(&mut writer, dialect.delimiter)?,
+    }
+    Ok(writer.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str =
+        "Name,Position,Kit Number\nBuffon,Goalkeeper,77\nHiguain,Forward,21\nDybala,Forward,21\n";
+    const NEW: &str = "Name,Kit Number,Position,Club\nDybala,10,Forward,Juventus\nBuffon,77,Goalkeeper,Juventus\nDemiral,28,Centre-Back,Juventus\n";
+
+    fn run() -> Result<CsvDiff> {
+        let dialect = CsvDialect::default();
+        let mut old = dialect.reader(OLD.as_bytes());
+        let old_headers = dialect.headers(&mut old)?;
+        let mut new = dialect.reader(NEW.as_bytes());
+        let new_headers = dialect.headers(&mut new)?;
+        diff(
+            &mut old,
+            old_headers,
+            &mut new,
+            new_headers,
+            &["Name".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_diff_human() -> Result<()> {
+        let diff = run()?;
+        assert_eq!(
+            diff.to_human(),
+            "+ column Club\n\
+             - Name=Higuain\n\
+             ~ Name=Dybala\n    Kit Number: \"21\" -> \"10\"\n\
+             + Name=Demiral\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_json_and_patch() -> Result<()> {
+        let diff = run()?;
+        let value = diff.to_json();
+        assert_eq!(value["columns_added"], json!(["Club"]));
+        assert_eq!(value["removed"][0]["Name"], json!("Higuain"));
+        assert_eq!(
+            value["changed"][0]["cells"]["Kit Number"],
+            json!({"before": "21", "after": "10"})
+        );
+
+        let mut patch = Vec::new();
+        diff.write_patch(&mut patch, b',')?;
+        assert_eq!(
+            String::from_utf8(patch)?,
+            "op,Name,Kit Number,Position,Club\n\
+             remove,Higuain,21,Forward,\n\
+             change,Dybala,10,Forward,Juventus\n\
+             add,Demiral,28,Centre-Back,Juventus\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_duplicate_key() {
+        let dialect = CsvDialect::default();
+        let mut old = dialect.reader(&b"Name\na\na\n"[..]);
+        let mut new = dialect.reader(&b"Name\na\n"[..]);
+        let headers = StringRecord::from(vec!["Name"]);
+        let result = diff(
+            &mut old,
+            headers.clone(),
+            &mut new,
+            headers,
+            &["Name".to_string()],
+        );
+        assert!(result.is_err());
+    }
+}
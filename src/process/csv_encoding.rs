@@ -0,0 +1,147 @@
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+const DETECT_SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// The encoding guessed for some input. `confidence` runs from 0 to 1: a BOM
+/// or valid UTF-8 is certain, anything else is a statistical guess.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detected {
+    pub encoding: &'static Encoding,
+    pub confidence: f32,
+}
+
+/// Guesses the encoding of `sample`, the first bytes of the input.
+pub fn detect(sample: &[u8]) -> Detected {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return Detected {
+            encoding,
+            confidence: 1.0,
+        };
+    }
+    if let Some(encoding) = detect_utf16(sample) {
+        return Detected {
+            encoding,
+            confidence: 0.9,
+        };
+    }
+    // a sample may end halfway through a character
+    let utf8 = std::str::from_utf8(sample).map_or_else(|e| e.error_len().is_none(), |_| true);
+    if utf8 {
+        return Detected {
+            encoding: UTF_8,
+            confidence: 1.0,
+        };
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, false);
+    let (encoding, sure) = detector.guess_assess(None, false);
+    Detected {
+        encoding,
+        confidence: if sure { 0.8 } else { 0.3 },
+    }
+}
+
+/// UTF-16 without a BOM: mostly-ascii text leaves every other byte zero.
+fn detect_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd * 2 > pairs && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 2 > pairs && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Transcodes `rdr` to UTF-8 and drops any BOM. Without an explicit
+/// `encoding` one is detected from the first 64KiB.
+pub fn decode_reader<'a, R: Read + 'a>(
+    mut rdr: R,
+    encoding: Option<&'static Encoding>,
+) -> Result<Box<dyn Read + 'a>> {
+    let mut sample = Vec::new();
+    (&mut rdr)
+        .take(DETECT_SAMPLE_SIZE)
+        .read_to_end(&mut sample)?;
+    let encoding = encoding.unwrap_or_else(|| {
+        let detected = detect(&sample);
+        if detected.encoding != UTF_8 {
+            eprintln!(
+                "Detected {} input (confidence {:.1}), use --encoding to override",
+                detected.encoding.name(),
+                detected.confidence
+            );
+        }
+        detected.encoding
+    });
+    let rdr = Cursor::new(sample).chain(rdr);
+    let mut builder = DecodeReaderBytesBuilder::new();
+    builder.strip_bom(true);
+    if encoding == UTF_8 {
+        // valid UTF-8 goes through untouched, the csv reader checks it anyway
+        builder.utf8_passthru(true);
+    } else {
+        builder.encoding(Some(encoding));
+    }
+    Ok(Box::new(builder.build(rdr)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{GBK, WINDOWS_1252};
+
+    fn decode(data: &[u8], encoding: Option<&'static Encoding>) -> Result<String> {
+        let mut out = String::new();
+        decode_reader(data, encoding)?.read_to_string(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_detect_bom_and_utf8() {
+        assert_eq!(detect(b"\xFF\xFEa\0").encoding, UTF_16LE);
+        assert_eq!(detect("Pelé".as_bytes()).encoding, UTF_8);
+        // cut in the middle of "é"
+        assert_eq!(detect(&"Pelé".as_bytes()[..4]).encoding, UTF_8);
+        assert_eq!(detect(b"N\0a\0m\0e\0").encoding, UTF_16LE);
+    }
+
+    #[test]
+    fn test_decode_utf16_with_bom() -> Result<()> {
+        // encoding_rs can't encode to utf-16, so build it by hand
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "Name\nPelé\n".encode_utf16() {
+            utf16.extend(unit.to_le_bytes());
+        }
+        assert_eq!(decode(&utf16, None)?, "Name\nPelé\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_legacy_encodings() -> Result<()> {
+        let (gbk, _, _) = GBK.encode("名字,国家\n布冯,意大利\n");
+        assert_eq!(decode(&gbk, Some(GBK))?, "名字,国家\n布冯,意大利\n");
+        let (latin, _, _) = WINDOWS_1252.encode("Name\nPelé\nMüller\n");
+        assert_eq!(detect(&latin).encoding, WINDOWS_1252);
+        assert_eq!(decode(&latin, None)?, "Name\nPelé\nMüller\n");
+        assert_eq!(decode(b"\xEF\xBB\xBFName\n", None)?, "Name\n");
+        Ok(())
+    }
+}
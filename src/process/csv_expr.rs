@@ -0,0 +1,301 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use anyhow::Result;
+use csv::StringRecord;
+
+/// A row filter such as `Position == "Goalkeeper" && Kit Number > 10`.
+///
+/// Bare words are column names (they may contain spaces), or number literals
+/// when no column has that name. Quote strings with `"` or `'`, and wrap odd
+/// column names in backticks. Two values compare as numbers when both parse
+/// as one, otherwise as strings. An empty cell has no order against a number,
+/// so `Kit Number < 10` skips rows without one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(usize),
+    Literal(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Cmp(CmpOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Column(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+enum Val<'a> {
+    Text(&'a str),
+    Bool(bool),
+}
+
+impl Expr {
+    pub fn parse(src: &str, headers: &StringRecord) -> Result<Self> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+            headers,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.next() {
+            anyhow::bail!("Unexpected {:?} in expression: {}", token, src);
+        }
+        Ok(expr)
+    }
+
+    pub fn matches(&self, record: &StringRecord) -> bool {
+        self.eval(record).truthy()
+    }
+
+    fn eval<'a>(&'a self, record: &'a StringRecord) -> Val<'a> {
+        match self {
+            Expr::Column(idx) => Val::Text(record.get(*idx).unwrap_or("")),
+            Expr::Literal(s) => Val::Text(s),
+            Expr::Not(e) => Val::Bool(!e.matches(record)),
+            Expr::And(a, b) => Val::Bool(a.matches(record) && b.matches(record)),
+            Expr::Or(a, b) => Val::Bool(a.matches(record) || b.matches(record)),
+            Expr::Cmp(op, a, b) => {
+                let ord = compare(&a.eval(record), &b.eval(record));
+                Val::Bool(match op {
+                    CmpOp::Eq => ord == Some(Ordering::Equal),
+                    CmpOp::Ne => ord != Some(Ordering::Equal),
+                    CmpOp::Lt => ord == Some(Ordering::Less),
+                    CmpOp::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                    CmpOp::Gt => ord == Some(Ordering::Greater),
+                    CmpOp::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+                })
+            }
+        }
+    }
+}
+
+impl Val<'_> {
+    fn truthy(&self) -> bool {
+        match self {
+            Val::Bool(b) => *b,
+            Val::Text(s) => !s.is_empty(),
+        }
+    }
+}
+
+fn compare(a: &Val, b: &Val) -> Option<Ordering> {
+    match (a, b) {
+        (Val::Text(a), Val::Text(b)) => match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            (Ok(_), Err(_)) if b.trim().is_empty() => None,
+            (Err(_), Ok(_)) if a.trim().is_empty() => None,
+            _ => Some(a.cmp(b)),
+        },
+        (a, b) => Some(a.truthy().cmp(&b.truthy())),
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            }
+            '"' | '\'' => tokens.push(Token::Quoted(read_quoted(&mut chars)?)),
+            '`' => tokens.push(Token::Column(read_quoted(&mut chars)?)),
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(c) {
+                    anyhow::bail!("Expected {}{} in expression: {}", c, c, src);
+                }
+                tokens.push(if c == '&' { Token::And } else { Token::Or });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(match (c, eq) {
+                    ('=', true) => Token::Op(CmpOp::Eq),
+                    ('=', false) => anyhow::bail!("Use == to compare in expression: {}", src),
+                    ('!', true) => Token::Op(CmpOp::Ne),
+                    ('!', false) => Token::Not,
+                    ('<', true) => Token::Op(CmpOp::Le),
+                    ('<', false) => Token::Op(CmpOp::Lt),
+                    ('>', true) => Token::Op(CmpOp::Ge),
+                    _ => Token::Op(CmpOp::Gt),
+                });
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !"()\"'`&|=!<>".contains(*c)) {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word.trim().to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String> {
+    let quote = chars.next();
+    let mut s = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => s.extend(chars.next()),
+            c if Some(c) == quote => return Ok(s),
+            c => s.push(c),
+        }
+    }
+    anyhow::bail!("Unterminated quote in expression")
+}
+
+struct Parser<'a> {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    headers: &'a StringRecord,
+}
+
+impl Parser<'_> {
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.tokens.next_if_eq(&Token::Or).is_some() {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.tokens.next_if_eq(&Token::And).is_some() {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.tokens.next_if_eq(&Token::Not).is_some() {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let lhs = self.primary()?;
+        match self.tokens.peek() {
+            Some(Token::Op(op)) => {
+                let op = *op;
+                self.tokens.next();
+                Ok(Expr::Cmp(op, Box::new(lhs), Box::new(self.primary()?)))
+            }
+            _ => Ok(lhs),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.tokens.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.tokens.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => anyhow::bail!("Missing ) in expression"),
+                }
+            }
+            Some(Token::Quoted(s)) => Ok(Expr::Literal(s)),
+            Some(Token::Column(name)) => self.column(&name),
+            Some(Token::Word(word)) => match self.column(&word) {
+                Ok(column) => Ok(column),
+                Err(_) if word.parse::<f64>().is_ok() => Ok(Expr::Literal(word)),
+                Err(e) => Err(e),
+            },
+            Some(token) => anyhow::bail!("Unexpected {:?} in expression", token),
+            None => anyhow::bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<Expr> {
+        match self.headers.iter().position(|h| h == name) {
+            Some(idx) => Ok(Expr::Column(idx)),
+            None => anyhow::bail!("Unknown column in expression: {}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers() -> StringRecord {
+        StringRecord::from(vec!["Name", "Position", "Kit Number"])
+    }
+
+    fn row(name: &str, position: &str, kit: &str) -> StringRecord {
+        StringRecord::from(vec![name, position, kit])
+    }
+
+    #[test]
+    fn test_expr_compare_and() -> Result<()> {
+        let expr = Expr::parse(r#"Position == "Goalkeeper" && Kit Number > 10"#, &headers())?;
+        assert!(expr.matches(&row("Mattia Perin", "Goalkeeper", "37")));
+        assert!(!expr.matches(&row("Wojciech Szczesny", "Goalkeeper", "1")));
+        assert!(!expr.matches(&row("Paulo Dybala", "Forward", "10")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_or_not_parens() -> Result<()> {
+        let expr = Expr::parse("!(`Kit Number` <= 9 || Position != 'Forward')", &headers())?;
+        assert!(expr.matches(&row("Paulo Dybala", "Forward", "10")));
+        assert!(!expr.matches(&row("Cristiano Ronaldo", "Forward", "7")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_vs_string_order() -> Result<()> {
+        let expr = Expr::parse("Kit Number < 9", &headers())?;
+        assert!(!expr.matches(&row("a", "b", "10")));
+        let expr = Expr::parse("Kit Number < '9'", &headers())?;
+        assert!(!expr.matches(&row("a", "b", "10")));
+        let expr = Expr::parse("Name < 'B'", &headers())?;
+        assert!(expr.matches(&row("Alex Sandro", "b", "12")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_cell_vs_number() -> Result<()> {
+        for src in ["Kit Number < 10", "Kit Number >= 10", "10 > Kit Number"] {
+            assert!(!Expr::parse(src, &headers())?.matches(&row("a", "b", "")));
+        }
+        let expr = Expr::parse("Kit Number != 10", &headers())?;
+        assert!(expr.matches(&row("a", "b", "")));
+        let expr = Expr::parse("Kit Number == ''", &headers())?;
+        assert!(expr.matches(&row("a", "b", "")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_errors() {
+        assert!(Expr::parse("Club == 'x'", &headers()).is_err());
+        assert!(Expr::parse("Name = 'x'", &headers()).is_err());
+        assert!(Expr::parse("(Name == 'x'", &headers()).is_err());
+        assert!(Expr::parse("Name == 'x", &headers()).is_err());
+    }
+}
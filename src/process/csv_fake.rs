@@ -0,0 +1,377 @@
+use std::{fmt::Write as _, io::Write};
+
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use csv::{Writer, WriterBuilder};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use serde::Deserialize;
+
+use crate::utils::{get_reader, get_writer};
+
+const FIRST_NAMES: &[&str] = &[
+    "Adrian", "Alessio", "Andre", "Bruno", "Carlos", "Cristian", "Daniel", "Dario", "Diego",
+    "Emil", "Enzo", "Fabio", "Federico", "Filip", "Gabriel", "Giorgio", "Hugo", "Ivan", "Jakub",
+    "Jonas", "Julian", "Karim", "Kevin", "Leon", "Lorenzo", "Luca", "Lucas", "Marco", "Mario",
+    "Mateo", "Matteo", "Nicolas", "Oscar", "Pablo", "Paolo", "Pedro", "Rafael", "Riccardo",
+    "Samuel", "Sergio", "Simone", "Stefan", "Thomas", "Tomas", "Victor", "Youssef",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Almeida",
+    "Bauer",
+    "Bianchi",
+    "Costa",
+    "Colombo",
+    "Dubois",
+    "Esposito",
+    "Fernandes",
+    "Ferrari",
+    "Fischer",
+    "Garcia",
+    "Gomez",
+    "Greco",
+    "Hansen",
+    "Jensen",
+    "Kowalski",
+    "Lambert",
+    "Lopez",
+    "Marino",
+    "Martin",
+    "Moreau",
+    "Moretti",
+    "Novak",
+    "Olsen",
+    "Pereira",
+    "Petrov",
+    "Ricci",
+    "Rivera",
+    "Romano",
+    "Rossi",
+    "Russo",
+    "Santos",
+    "Schmidt",
+    "Silva",
+    "Torres",
+    "Vidal",
+    "Villa",
+    "Weber",
+    "Wojcik",
+    "Zanetti",
+];
+
+const NATIONALITIES: &[&str] = &[
+    "Argentina",
+    "Austria",
+    "Belgium",
+    "Bosnia-Herzegovina",
+    "Brazil",
+    "Cameroon",
+    "Colombia",
+    "Croatia",
+    "Czech Republic",
+    "Denmark",
+    "England",
+    "France",
+    "Germany",
+    "Ghana",
+    "Greece",
+    "Italy",
+    "Ivory Coast",
+    "Japan",
+    "Mexico",
+    "Morocco",
+    "Netherlands",
+    "Nigeria",
+    "Norway",
+    "Poland",
+    "Portugal",
+    "Senegal",
+    "Serbia",
+    "Spain",
+    "Sweden",
+    "Switzerland",
+    "Turkey",
+    "Uruguay",
+    "USA",
+    "Wales",
+];
+
+#[derive(Debug, Deserialize)]
+struct FakeSchema {
+    columns: Vec<FakeColumn>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FakeColumn {
+    name: String,
+    #[serde(flatten)]
+    kind: FakeKind,
+}
+
+/// How a column's values are made up, as written in the schema file.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum FakeKind {
+    /// A first and last name.
+    Name,
+    Nationality,
+    /// A day between `from` and `to` inclusive, both `YYYY-MM-DD`, written
+    /// with a chrono `format`.
+    Date {
+        #[serde(default = "default_date_format")]
+        format: String,
+        from: String,
+        to: String,
+    },
+    /// One of `values`, picked in proportion to `weights` when given.
+    Enum {
+        values: Vec<String>,
+        #[serde(default)]
+        weights: Vec<f64>,
+    },
+    /// A whole number between `min` and `max` inclusive.
+    Int {
+        min: i64,
+        max: i64,
+    },
+    /// Sequential ids from `start`, zero padded to `width` digits.
+    Id {
+        #[serde(default)]
+        prefix: String,
+        #[serde(default = "default_id_start")]
+        start: u64,
+        #[serde(default)]
+        width: usize,
+    },
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+fn default_id_start() -> u64 {
+    1
+}
+
+/// A checked `FakeKind`, ready to draw values from.
+enum Generator {
+    Name,
+    Nationality,
+    Date {
+        format: String,
+        from: NaiveDate,
+        days: u64,
+    },
+    Enum {
+        values: Vec<String>,
+        weights: Option<WeightedIndex<f64>>,
+    },
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Id {
+        prefix: String,
+        start: u64,
+        width: usize,
+    },
+}
+
+impl Generator {
+    fn try_new(column: &FakeColumn) -> Result<Self> {
+        let fail = |msg: String| anyhow::anyhow!("Column {:?}: {}", column.name, msg);
+        let generator = match &column.kind {
+            FakeKind::Name => Generator::Name,
+            FakeKind::Nationality => Generator::Nationality,
+            FakeKind::Date { format, from, to } => {
+                let parse = |date: &str| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .map_err(|_| fail(format!("{} is not a YYYY-MM-DD date", date)))
+                };
+                let (from, to) = (parse(from)?, parse(to)?);
+                if from > to {
+                    return Err(fail("from is after to".to_string()));
+                }
+                let mut check = String::new();
+                write!(check, "{}", from.format(format))
+                    .map_err(|_| fail(format!("Invalid date format {:?}", format)))?;
+                Generator::Date {
+                    format: format.clone(),
+                    from,
+                    days: (to - from).num_days() as u64,
+                }
+            }
+            FakeKind::Enum { values, weights } => {
+                if values.is_empty() {
+                    return Err(fail("an enum needs values".to_string()));
+                }
+                let weights = match weights.len() {
+                    0 => None,
+                    n if n == values.len() => Some(
+                        WeightedIndex::new(weights).map_err(|e| fail(format!("weights: {}", e)))?,
+                    ),
+                    _ => return Err(fail("give one weight per value".to_string())),
+                };
+                Generator::Enum {
+                    values: values.clone(),
+                    weights,
+                }
+            }
+            FakeKind::Int { min, max } => {
+                if min > max {
+                    return Err(fail("min is above max".to_string()));
+                }
+                Generator::Int {
+                    min: *min,
+                    max: *max,
+                }
+            }
+            FakeKind::Id {
+                prefix,
+                start,
+                width,
+            } => Generator::Id {
+                prefix: prefix.clone(),
+                start: *start,
+                width: *width,
+            },
+        };
+        Ok(generator)
+    }
+
+    /// The value for row `row`, counting from 0.
+    fn value(&self, rng: &mut StdRng, row: u64) -> String {
+        let pick = |rng: &mut StdRng, items: &[&str]| {
+            items.choose(rng).copied().unwrap_or_default().to_string()
+        };
+        match self {
+            Generator::Name => {
+                let first = pick(rng, FIRST_NAMES);
+                format!("{} {}", first, pick(rng, LAST_NAMES))
+            }
+            Generator::Nationality => pick(rng, NATIONALITIES),
+            Generator::Date { format, from, days } => {
+                let date = *from + Days::new(rng.gen_range(0..=*days));
+                date.format(format).to_string()
+            }
+            Generator::Enum { values, weights } => {
+                let i = match weights {
+                    Some(weights) => weights.sample(rng),
+                    None => rng.gen_range(0..values.len()),
+                };
+                values[i].clone()
+            }
+            Generator::Int { min, max } => rng.gen_range(*min..=*max).to_string(),
+            Generator::Id {
+                prefix,
+                start,
+                width,
+            } => format!("{}{:0width$}", prefix, start + row, width = *width),
+        }
+    }
+}
+
+/// Writes a header and `rows` rows of made up values. The same schema and
+/// seed always give the same rows.
+fn write_fake<W: Write>(
+    schema: &FakeSchema,
+    rows: u64,
+    seed: u64,
+    writer: &mut Writer<W>,
+) -> Result<()> {
+    let generators = schema
+        .columns
+        .iter()
+        .map(Generator::try_new)
+        .collect::<Result<Vec<_>>>()?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    writer.write_record(schema.columns.iter().map(|c| &c.name))?;
+    for row in 0..rows {
+        writer.write_record(generators.iter().map(|g| g.value(&mut rng, row)))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Generates csv test data from a yaml schema. Without a seed one is picked
+/// at random and printed, so the run can be repeated.
+pub fn process_csv_fake(
+    schema: &str,
+    output: &str,
+    rows: u64,
+    seed: Option<u64>,
+    delimiter: u8,
+) -> Result<()> {
+    let schema: FakeSchema = serde_yaml::from_reader(get_reader(schema)?)?;
+    if schema.columns.is_empty() {
+        anyhow::bail!("The schema has no columns");
+    }
+    let seed = seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        eprintln!("Seed: {}", seed);
+        seed
+    });
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(get_writer(output)?);
+    write_fake(&schema, rows, seed, &mut writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn fake(schema: &FakeSchema, rows: u64, seed: u64) -> Result<String> {
+        let mut writer = Writer::from_writer(Vec::new());
+        write_fake(schema, rows, seed, &mut writer)?;
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+
+    fn juventus() -> Result<FakeSchema> {
+        Ok(serde_yaml::from_reader(get_reader(
+            "assets/juventus-fake.yaml",
+        )?)?)
+    }
+
+    #[test]
+    fn test_seed_is_reproducible() -> Result<()> {
+        let schema = juventus()?;
+        assert_eq!(fake(&schema, 50, 7)?, fake(&schema, 50, 7)?);
+        assert_ne!(fake(&schema, 50, 7)?, fake(&schema, 50, 8)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_values_follow_schema() -> Result<()> {
+        let data = fake(&juventus()?, 500, 42)?;
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        assert_eq!(
+            reader.headers()?,
+            vec!["Id", "Name", "Position", "DOB", "Nationality", "Kit Number"]
+        );
+        let mut ids = HashSet::new();
+        for record in reader.records() {
+            let record = record?;
+            assert!(ids.insert(record[0].to_string()));
+            assert!(record[0].starts_with('P') && record[0].len() == 7);
+            assert!(["Goalkeeper", "Defender", "Midfielder", "Forward"].contains(&&record[2]));
+            let dob = NaiveDate::parse_from_str(&record[3], "%b %d, %Y")?;
+            assert!(dob.to_string().as_str() >= "1978-01-01");
+            assert!(dob.to_string().as_str() <= "2002-12-31");
+            assert!(NATIONALITIES.contains(&&record[4]));
+            assert!((1..=99).contains(&record[5].parse::<i64>()?));
+        }
+        assert_eq!(ids.len(), 500);
+
+        let bad: FakeSchema =
+            serde_yaml::from_str("columns: [{name: n, kind: int, min: 5, max: 1}]")?;
+        assert!(fake(&bad, 1, 0).is_err());
+        Ok(())
+    }
+}
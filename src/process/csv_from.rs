@@ -0,0 +1,210 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Read},
+};
+
+use anyhow::Result;
+use csv::WriterBuilder;
+use serde_json::Value;
+
+use crate::{
+    cli::{ArrayMode, InputFormat},
+    utils::{get_reader, get_writer},
+};
+
+type Row = Vec<(String, String)>;
+
+/// Turns json/yaml/ndjson records back into csv. The header is the union of
+/// every record's keys, nested objects become dotted column names.
+pub fn process_csv_from(
+    input: &str,
+    output: &str,
+    format: InputFormat,
+    arrays: ArrayMode,
+    delimiter: u8,
+) -> Result<()> {
+    let reader = get_reader(input)?;
+    let records = read_records(reader, format)?;
+
+    let mut rows = Vec::with_capacity(records.len());
+    for record in &records {
+        rows.extend(flatten_record(record, arrays));
+    }
+    let headers = union_headers(&rows);
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(get_writer(output)?);
+    writer.write_record(&headers)?;
+    for row in rows {
+        let cells: HashMap<&str, &str> =
+            row.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        writer.write_record(
+            headers
+                .iter()
+                .map(|name| cells.get(name.as_str()).unwrap_or(&"")),
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_records(mut reader: Box<dyn Read>, format: InputFormat) -> Result<Vec<Value>> {
+    let value = match format {
+        InputFormat::Json => serde_json::from_reader(reader)?,
+        InputFormat::Yaml => {
+            let mut buf = String::new();
+            reader.read_to_string(&mut buf)?;
+            serde_yaml::from_str(&buf)?
+        }
+        InputFormat::Ndjson => {
+            let mut records = Vec::new();
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line)?);
+                }
+            }
+            return Ok(records);
+        }
+    };
+    Ok(match value {
+        Value::Array(records) => records,
+        v => vec![v],
+    })
+}
+
+/// Flattens one record into one or more rows. Exploding arrays yields a row
+/// per element, so several array fields multiply out; indexing them yields a
+/// `name[i]` column per element instead.
+fn flatten_record(record: &Value, arrays: ArrayMode) -> Vec<Row> {
+    match record {
+        Value::Object(_) => flatten_value("", record, arrays, vec![Vec::new()]),
+        v => flatten_value("value", v, arrays, vec![Vec::new()]),
+    }
+}
+
+fn flatten_value(prefix: &str, value: &Value, arrays: ArrayMode, rows: Vec<Row>) -> Vec<Row> {
+    match value {
+        Value::Object(map) => map.iter().fold(rows, |rows, (k, v)| {
+            let name = if prefix.is_empty() {
+                k.clone()
+            } else {
+                format!("{}.{}", prefix, k)
+            };
+            flatten_value(&name, v, arrays, rows)
+        }),
+        Value::Array(items) if arrays == ArrayMode::Explode && !items.is_empty() => rows
+            .into_iter()
+            .flat_map(|row| {
+                items
+                    .iter()
+                    .flat_map(move |item| flatten_value(prefix, item, arrays, vec![row.clone()]))
+            })
+            .collect(),
+        Value::Array(items) if arrays == ArrayMode::Index && !items.is_empty() => {
+            items.iter().enumerate().fold(rows, |rows, (i, item)| {
+                flatten_value(&format!("{}[{}]", prefix, i), item, arrays, rows)
+            })
+        }
+        v => {
+            let cell = cell_string(v);
+            rows.into_iter()
+                .map(|mut row| {
+                    row.push((prefix.to_string(), cell.clone()));
+                    row
+                })
+                .collect()
+        }
+    }
+}
+
+fn cell_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) if items.is_empty() => String::new(),
+        v => v.to_string(),
+    }
+}
+
+fn union_headers(rows: &[Row]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut headers = Vec::new();
+    for (name, _) in rows.iter().flatten() {
+        if seen.insert(name.as_str()) {
+            headers.push(name.clone());
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cells(rows: &[Row]) -> Vec<Vec<(&str, &str)>> {
+        rows.iter()
+            .map(|row| row.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_flatten_nested_json_arrays() {
+        let record = json!({"name": "a", "address": {"city": "Turin"}, "tags": ["x", "y"]});
+        let rows = flatten_record(&record, ArrayMode::Json);
+        assert_eq!(
+            cells(&rows),
+            vec![vec![
+                ("name", "a"),
+                ("address.city", "Turin"),
+                ("tags", "[\"x\",\"y\"]")
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_flatten_explode() {
+        let record = json!({"name": "a", "tags": ["x", "y"], "pets": [{"kind": "cat"}]});
+        let rows = flatten_record(&record, ArrayMode::Explode);
+        assert_eq!(
+            cells(&rows),
+            vec![
+                vec![("name", "a"), ("tags", "x"), ("pets.kind", "cat")],
+                vec![("name", "a"), ("tags", "y"), ("pets.kind", "cat")],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flatten_index() {
+        let record = json!({"tags": ["x", "y"], "pets": [{"kind": "cat"}]});
+        let rows = flatten_record(&record, ArrayMode::Index);
+        assert_eq!(
+            cells(&rows),
+            vec![vec![
+                ("tags[0]", "x"),
+                ("tags[1]", "y"),
+                ("pets[0].kind", "cat")
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_union_headers() {
+        let rows = [json!({"a": 1, "b": null}), json!({"c": true, "a": 2})]
+            .iter()
+            .flat_map(|r| flatten_record(r, ArrayMode::Json))
+            .collect::<Vec<_>>();
+        assert_eq!(union_headers(&rows), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_read_ndjson() -> Result<()> {
+        let input = "{\"a\":1}\n\n{\"a\":2}\n";
+        let records = read_records(Box::new(input.as_bytes()), InputFormat::Ndjson)?;
+        assert_eq!(records, vec![json!({"a": 1}), json!({"a": 2})]);
+        Ok(())
+    }
+}
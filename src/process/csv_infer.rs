@@ -0,0 +1,215 @@
+use std::io::Read;
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+use serde_json::{Map, Number, Value};
+
+use super::csv_select::Projection;
+use crate::cli::ColumnType;
+
+/// Which columns get typed values instead of plain strings: every column when
+/// `infer` is set, plus any column named in `overrides`.
+#[derive(Debug, Default, Clone)]
+pub struct TypeHints {
+    pub infer: bool,
+    pub overrides: Vec<(String, ColumnType)>,
+}
+
+impl TypeHints {
+    /// Resolves a type for each column. `reader` is only consumed when whole
+    /// file inference is on, so callers hand in a fresh reader over the input.
+    pub fn resolve<R: Read>(
+        &self,
+        headers: &StringRecord,
+        reader: impl FnOnce() -> Result<Reader<R>>,
+    ) -> Result<Vec<Option<ColumnType>>> {
+        self.resolve_records(headers, || {
+            Ok(reader()?.into_records().map(|record| Ok(record?)))
+        })
+    }
+
+    /// Like `resolve`, for records that don't come straight from a reader.
+    pub fn resolve_records<I: Iterator<Item = Result<StringRecord>>>(
+        &self,
+        headers: &StringRecord,
+        records: impl FnOnce() -> Result<I>,
+    ) -> Result<Vec<Option<ColumnType>>> {
+        let mut types = if self.infer {
+            infer_record_types(records()?, headers.len())?
+                .into_iter()
+                .map(Some)
+                .collect()
+        } else {
+            vec![None; headers.len()]
+        };
+        for (name, ty) in &self.overrides {
+            let Some(idx) = headers.iter().position(|h| h == name) else {
+                anyhow::bail!("Unknown column in --type: {}", name);
+            };
+            types[idx] = Some(*ty);
+        }
+        Ok(types)
+    }
+}
+
+/// The narrowest type a single cell fits in; `None` for an empty cell.
+pub fn infer_cell(cell: &str) -> Option<ColumnType> {
+    let cell = cell.trim();
+    if cell.is_empty() {
+        None
+    } else if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+        Some(ColumnType::Bool)
+    } else if cell.parse::<i64>().is_ok() {
+        Some(ColumnType::Int)
+    } else if cell.parse::<f64>().is_ok_and(f64::is_finite) {
+        Some(ColumnType::Float)
+    } else {
+        Some(ColumnType::String)
+    }
+}
+
+/// Widens a column type so it also covers `cell`.
+pub fn merge_type(current: Option<ColumnType>, cell: Option<ColumnType>) -> Option<ColumnType> {
+    match (current, cell) {
+        (None, ty) | (ty, None) => ty,
+        (Some(a), Some(b)) if a == b => Some(a),
+        (Some(ColumnType::Int), Some(ColumnType::Float))
+        | (Some(ColumnType::Float), Some(ColumnType::Int)) => Some(ColumnType::Float),
+        _ => Some(ColumnType::String),
+    }
+}
+
+/// Scans every record and returns the narrowest type that fits each column.
+/// Columns that are empty throughout come out as strings.
+pub fn infer_record_types(
+    records: impl Iterator<Item = Result<StringRecord>>,
+    len: usize,
+) -> Result<Vec<ColumnType>> {
+    let mut types = vec![None; len];
+    for record in records {
+        let record = record?;
+        for (ty, cell) in types.iter_mut().zip(record.iter()) {
+            *ty = merge_type(*ty, infer_cell(cell));
+        }
+    }
+    Ok(types
+        .into_iter()
+        .map(|ty| ty.unwrap_or(ColumnType::String))
+        .collect())
+}
+
+/// Converts a cell to a json value of the given type, or `null` when empty.
+/// Untyped cells stay strings as they are.
+pub fn typed_value(cell: &str, ty: Option<ColumnType>) -> Result<Value> {
+    let Some(ty) = ty else {
+        return Ok(Value::String(cell.to_string()));
+    };
+    let trimmed = cell.trim();
+    if trimmed.is_empty() {
+        return Ok(Value::Null);
+    }
+    let value = match ty {
+        ColumnType::String => Value::String(cell.to_string()),
+        ColumnType::Int => Value::from(trimmed.parse::<i64>()?),
+        ColumnType::Float => {
+            let f = trimmed.parse::<f64>()?;
+            Value::Number(
+                Number::from_f64(f).ok_or_else(|| anyhow::anyhow!("Invalid float: {}", cell))?,
+            )
+        }
+        ColumnType::Bool => match trimmed.to_lowercase().as_str() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            v => anyhow::bail!("Invalid bool: {}", v),
+        },
+    };
+    Ok(value)
+}
+
+/// Builds a json object for one record from the projected columns, typing
+/// each cell by its column.
+pub fn typed_row(
+    projection: &Projection,
+    record: &StringRecord,
+    types: &[Option<ColumnType>],
+) -> Result<Map<String, Value>> {
+    projection
+        .iter()
+        .filter_map(|(idx, name)| Some((name, record.get(idx)?, types[idx])))
+        .map(|(name, cell, ty)| {
+            let value = typed_value(cell, ty).map_err(|e| {
+                let line = record.position().map_or(0, |p| p.line());
+                anyhow::anyhow!("Line {}, column {:?}: {}", line, name, e)
+            })?;
+            Ok((name.to_string(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CsvDialect;
+
+    #[test]
+    fn test_infer_cell() {
+        assert_eq!(infer_cell(""), None);
+        assert_eq!(infer_cell("TRUE"), Some(ColumnType::Bool));
+        assert_eq!(infer_cell("-12"), Some(ColumnType::Int));
+        assert_eq!(infer_cell("1.5"), Some(ColumnType::Float));
+        assert_eq!(infer_cell("NaN"), Some(ColumnType::String));
+        assert_eq!(infer_cell("Italy"), Some(ColumnType::String));
+    }
+
+    #[test]
+    fn test_infer_juventus() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let headers = dialect.headers(&mut reader)?;
+        let types = infer_record_types(reader.records().map(|r| Ok(r?)), headers.len())?;
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::String,
+                ColumnType::String,
+                ColumnType::String,
+                ColumnType::String,
+                ColumnType::Int
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_row() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader(&b"a,b,c,d\n1,2.5,,x\n2,3,true,y\n"[..]);
+        let headers = dialect.headers(&mut reader)?;
+        let hints = TypeHints {
+            infer: true,
+            overrides: vec![("d".to_string(), ColumnType::String)],
+        };
+        let types = hints.resolve(&headers, || {
+            Ok(dialect.reader(&b"a,b,c,d\n1,2.5,,x\n2,3,true,y\n"[..]))
+        })?;
+        let record = reader.records().next().expect("one record")?;
+        let projection = Projection::all(&headers);
+        let row = Value::Object(typed_row(&projection, &record, &types)?);
+        assert_eq!(
+            row,
+            serde_json::json!({"a": 1, "b": 2.5, "c": null, "d": "x"})
+        );
+
+        // keys follow the selected order, not the header's or the alphabet's
+        let projection = Projection::new(&headers, &["d".to_string(), "a".to_string()], &[])?;
+        let row = typed_row(&projection, &record, &types)?;
+        assert_eq!(serde_json::to_string(&row)?, r#"{"d":"x","a":1}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_override_mismatch() {
+        assert!(typed_value("abc", Some(ColumnType::Int)).is_err());
+        assert_eq!(typed_value(" 7 ", None).ok(), Some(Value::from(" 7 ")));
+    }
+}
@@ -0,0 +1,282 @@
+use std::{collections::HashMap, io::Read};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord, WriterBuilder};
+use serde_json::{Map, Value};
+
+use super::{csv_output::row_writer, CsvDialect};
+use crate::{
+    cli::{JoinKind, OutputFormat},
+    utils::get_writer,
+};
+
+/// An output row; `None` where the side a cell comes from had no match.
+type Row = Vec<Option<String>>;
+
+#[derive(Debug, Clone)]
+pub struct JoinOptions {
+    pub kind: JoinKind,
+    pub left_on: Vec<String>,
+    pub right_on: Vec<String>,
+    pub left_prefix: String,
+    pub right_prefix: String,
+}
+
+/// Where each output column comes from. The output has every left column,
+/// key columns included, followed by the right columns that aren't keys.
+#[derive(Debug)]
+struct JoinPlan {
+    headers: Vec<String>,
+    left_width: usize,
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    right_rest: Vec<usize>,
+}
+
+impl JoinPlan {
+    fn new(left: &StringRecord, right: &StringRecord, opts: &JoinOptions) -> Result<Self> {
+        if opts.left_on.is_empty() || opts.left_on.len() != opts.right_on.len() {
+            anyhow::bail!("Join needs the same number of key columns on both sides");
+        }
+        let position = |headers: &StringRecord, name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown column: {}", name))
+        };
+        let left_keys = opts
+            .left_on
+            .iter()
+            .map(|name| position(left, name))
+            .collect::<Result<Vec<_>>>()?;
+        let right_keys = opts
+            .right_on
+            .iter()
+            .map(|name| position(right, name))
+            .collect::<Result<Vec<_>>>()?;
+        let right_rest = (0..right.len())
+            .filter(|i| !right_keys.contains(i))
+            .collect::<Vec<_>>();
+
+        // a non-key column on both sides gets prefixed on both sides, a right
+        // column named like a left key only on the right
+        let clashes = |name: &str| right_rest.iter().any(|&i| &right[i] == name);
+        let mut headers = left
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if !left_keys.contains(&i) && clashes(name) {
+                    format!("{}{}", opts.left_prefix, name)
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect::<Vec<_>>();
+        for &i in &right_rest {
+            let name = &right[i];
+            if left.iter().any(|h| h == name) {
+                headers.push(format!("{}{}", opts.right_prefix, name));
+            } else {
+                headers.push(name.to_string());
+            }
+        }
+        Ok(Self {
+            headers,
+            left_width: left.len(),
+            left_keys,
+            right_keys,
+            right_rest,
+        })
+    }
+
+    fn key(record: &StringRecord, keys: &[usize]) -> Vec<String> {
+        keys.iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect()
+    }
+
+    fn row(&self, left: Option<&StringRecord>, right: Option<&StringRecord>) -> Row {
+        let mut row = match left {
+            Some(left) => (0..self.left_width)
+                .map(|i| Some(left.get(i).unwrap_or("").to_string()))
+                .collect(),
+            None => vec![None; self.left_width],
+        };
+        if let (None, Some(right)) = (left, right) {
+            for (&l, &r) in self.left_keys.iter().zip(&self.right_keys) {
+                row[l] = right.get(r).map(String::from);
+            }
+        }
+        row.extend(
+            self.right_rest
+                .iter()
+                .map(|&i| right.map(|r| r.get(i).unwrap_or("").to_string())),
+        );
+        row
+    }
+}
+
+/// Joins two readers as `plan` lays out, handing every output row to `emit`. The right side
+/// is held in memory, the left side is streamed.
+fn join<L: Read, R: Read>(
+    left: &mut Reader<L>,
+    right: &mut Reader<R>,
+    plan: &JoinPlan,
+    kind: JoinKind,
+    mut emit: impl FnMut(Row) -> Result<()>,
+) -> Result<()> {
+    let right_rows = right.records().collect::<Result<Vec<_>, _>>()?;
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (i, record) in right_rows.iter().enumerate() {
+        index
+            .entry(JoinPlan::key(record, &plan.right_keys))
+            .or_default()
+            .push(i);
+    }
+
+    let keep_left = matches!(kind, JoinKind::Left | JoinKind::Full);
+    let keep_right = matches!(kind, JoinKind::Right | JoinKind::Full);
+    let mut matched = vec![false; right_rows.len()];
+    for record in left.records() {
+        let record = record?;
+        match index.get(&JoinPlan::key(&record, &plan.left_keys)) {
+            Some(rows) => {
+                for &i in rows {
+                    matched[i] = true;
+                    emit(plan.row(Some(&record), Some(&right_rows[i])))?;
+                }
+            }
+            None if keep_left => emit(plan.row(Some(&record), None))?,
+            None => {}
+        }
+    }
+    if keep_right {
+        for (record, _) in right_rows.iter().zip(&matched).filter(|(_, m)| !**m) {
+            emit(plan.row(None, Some(record)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Joins `left` and `right`, each read with its own dialect, and writes the
+/// result as csv in the left dialect, or as records in `format` when one is
+/// given.
+pub fn process_csv_join(
+    left: &str,
+    right: &str,
+    output: &str,
+    format: Option<OutputFormat>,
+    toml_key: &str,
+    [dialect, right_dialect]: [&CsvDialect; 2],
+    opts: &JoinOptions,
+) -> Result<()> {
+    let mut left_reader = dialect.open(left)?;
+    let left_headers = dialect.headers(&mut left_reader)?;
+    let mut right_reader = right_dialect.open(right)?;
+    let right_headers = right_dialect.headers(&mut right_reader)?;
+    let plan = JoinPlan::new(&left_headers, &right_headers, opts)?;
+    let out = get_writer(output)?;
+
+    let Some(format) = format else {
+        let mut writer = WriterBuilder::new()
+            .delimiter(dialect.delimiter)
+            .from_writer(out);
+        writer.write_record(&plan.headers)?;
+        join(
+            &mut left_reader,
+            &mut right_reader,
+            &plan,
+            opts.kind,
+            |row| Ok(writer.write_record(row.iter().map(|c| c.as_deref().unwrap_or("")))?),
+        )?;
+        writer.flush()?;
+        return Ok(());
+    };
+
+    let mut writer = row_writer(format, out, toml_key);
+    join(
+        &mut left_reader,
+        &mut right_reader,
+        &plan,
+        opts.kind,
+        |row| {
+            let row = plan
+                .headers
+                .iter()
+                .zip(row)
+                .map(|(name, cell)| (name.clone(), cell.map_or(Value::Null, Value::String)))
+                .collect::<Map<_, _>>();
+            writer.write_row(Value::Object(row))
+        },
+    )?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYERS: &str =
+        "Name,Position,Club\nPerin,Goalkeeper,Juventus\nDybala,Forward,Juventus\n";
+    const CAPS: &str = "Player,Caps,Club\nDybala,34,Argentina\nBuffon,176,Italy\n";
+
+    fn run(kind: JoinKind) -> Result<(Vec<String>, Vec<Row>)> {
+        let dialect = CsvDialect::default();
+        let mut left = dialect.reader(PLAYERS.as_bytes());
+        let left_headers = dialect.headers(&mut left)?;
+        let mut right = dialect.reader(CAPS.as_bytes());
+        let right_headers = dialect.headers(&mut right)?;
+        let opts = JoinOptions {
+            kind,
+            left_on: vec!["Name".to_string()],
+            right_on: vec!["Player".to_string()],
+            left_prefix: "left_".to_string(),
+            right_prefix: "right_".to_string(),
+        };
+        let mut rows = Vec::new();
+        let plan = JoinPlan::new(&left_headers, &right_headers, &opts)?;
+        join(&mut left, &mut right, &plan, kind, |row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        Ok((plan.headers, rows))
+    }
+
+    fn cells(row: &[&str]) -> Row {
+        row.iter()
+            .map(|c| (!c.is_empty()).then(|| c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_inner_join_prefixes_clashes() -> Result<()> {
+        let (headers, rows) = run(JoinKind::Inner)?;
+        assert_eq!(
+            headers,
+            vec!["Name", "Position", "left_Club", "Caps", "right_Club"]
+        );
+        assert_eq!(
+            rows,
+            vec![cells(&["Dybala", "Forward", "Juventus", "34", "Argentina"])]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_full_join() -> Result<()> {
+        let (_, rows) = run(JoinKind::Full)?;
+        assert_eq!(
+            rows,
+            vec![
+                cells(&["Perin", "Goalkeeper", "Juventus", "", ""]),
+                cells(&["Dybala", "Forward", "Juventus", "34", "Argentina"]),
+                cells(&["Buffon", "", "", "176", "Italy"]),
+            ]
+        );
+        let (_, rows) = run(JoinKind::Left)?;
+        assert_eq!(rows.len(), 2);
+        let (_, rows) = run(JoinKind::Right)?;
+        assert_eq!(rows.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+/// Largest array index a header may use, so a typo can't allocate gigabytes.
+const MAX_INDEX: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Splits a header like `address.city` or `tags[0].name` into its path.
+/// Brackets around anything but a number are kept as part of the key, so
+/// headers like `Weight [kg]` stay flat.
+fn parse_path(name: &str) -> Vec<Segment> {
+    let mut path = Vec::new();
+    let mut key = String::new();
+    let mut rest = name;
+    while let Some(c) = rest.chars().next() {
+        let index = rest.strip_prefix('[').and_then(|after| {
+            let (digits, tail) = after.split_once(']')?;
+            let i = digits.parse::<usize>().ok()?;
+            Some((i, tail))
+        });
+        if let Some((i, tail)) = index {
+            if !key.is_empty() {
+                path.push(Segment::Key(std::mem::take(&mut key)));
+            }
+            path.push(Segment::Index(i));
+            rest = tail.strip_prefix('.').unwrap_or(tail);
+            continue;
+        }
+        if c == '.' {
+            path.push(Segment::Key(std::mem::take(&mut key)));
+        } else {
+            key.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    if !key.is_empty() || path.is_empty() {
+        path.push(Segment::Key(key));
+    }
+    path
+}
+
+fn insert(target: &mut Value, path: &[Segment], value: Value, name: &str) -> Result<()> {
+    let conflict = || anyhow::anyhow!("Column {:?} conflicts with another column", name);
+    match path.split_first() {
+        None if target.is_object() || target.is_array() => Err(conflict()),
+        None => {
+            *target = value;
+            Ok(())
+        }
+        Some((Segment::Key(key), rest)) => {
+            if target.is_null() {
+                *target = Value::Object(Map::new());
+            }
+            let Value::Object(map) = target else {
+                return Err(conflict());
+            };
+            insert(
+                map.entry(key.clone()).or_insert(Value::Null),
+                rest,
+                value,
+                name,
+            )
+        }
+        Some((Segment::Index(i), rest)) => {
+            if *i > MAX_INDEX {
+                anyhow::bail!("Array index too large in column {:?}", name);
+            }
+            if target.is_null() {
+                *target = Value::Array(Vec::new());
+            }
+            let Value::Array(items) = target else {
+                return Err(conflict());
+            };
+            if items.len() <= *i {
+                items.resize(i + 1, Value::Null);
+            }
+            insert(&mut items[*i], rest, value, name)
+        }
+    }
+}
+
+/// Whether `unflatten` moves the column `name` into a nested object or array.
+pub fn is_nested(name: &str) -> bool {
+    parse_path(name).len() > 1
+}
+
+/// Turns a flat row into nested objects and arrays by reading its keys as
+/// paths. Array slots no column fills are `null`.
+pub fn unflatten(row: Map<String, Value>) -> Result<Value> {
+    let mut nested = Value::Object(Map::new());
+    for (name, value) in row {
+        let path = parse_path(&name);
+        if matches!(path.first(), Some(Segment::Index(_))) {
+            anyhow::bail!("Column {:?} must start with a name", name);
+        }
+        insert(&mut nested, &path, value, &name)?;
+    }
+    Ok(nested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_path() {
+        use Segment::*;
+        assert_eq!(
+            parse_path("pets[1].kind"),
+            vec![Key("pets".into()), Index(1), Key("kind".into())]
+        );
+        assert_eq!(parse_path("Weight [kg]"), vec![Key("Weight [kg]".into())]);
+        assert!(is_nested("player.id") && !is_nested("Weight [kg]"));
+        assert_eq!(
+            parse_path("m[0][2]"),
+            vec![Key("m".into()), Index(0), Index(2)]
+        );
+    }
+
+    #[test]
+    fn test_unflatten() -> Result<()> {
+        let flat = row(json!({
+            "name": "Buffon",
+            "address.city": "Turin",
+            "address.zip": "10151",
+            "tags[0]": "captain",
+            "tags[2]": "legend",
+            "pets[0].kind": "dog",
+        }));
+        assert_eq!(
+            unflatten(flat)?,
+            json!({
+                "name": "Buffon",
+                "address": {"city": "Turin", "zip": "10151"},
+                "tags": ["captain", null, "legend"],
+                "pets": [{"kind": "dog"}],
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unflatten_conflicts() {
+        assert!(unflatten(row(json!({"a": 1, "a.b": 2}))).is_err());
+        assert!(unflatten(row(json!({"a[0]": 1, "a.b": 2}))).is_err());
+        assert!(unflatten(row(json!({"[0]": 1}))).is_err());
+    }
+}
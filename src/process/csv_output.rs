@@ -0,0 +1,445 @@
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::cli::{OutputFormat, Shape};
+
+/// Sink for converted rows. Json and ndjson rows are written as they arrive;
+/// yaml and toml can't be emitted piecemeal, so they are collected and
+/// written on `finish`.
+pub trait RowWriter {
+    fn write_row(&mut self, row: Value) -> Result<()>;
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+pub fn row_writer<'a>(
+    format: OutputFormat,
+    out: Box<dyn Write + 'a>,
+    toml_key: &str,
+) -> Box<dyn RowWriter + 'a> {
+    match format {
+        OutputFormat::Json => Box::new(JsonArrayWriter { out, rows: 0 }),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter { out }),
+        OutputFormat::Yaml | OutputFormat::Toml => Box::new(BufferedWriter {
+            out,
+            format,
+            toml_key: toml_key.to_string(),
+            rows: Vec::with_capacity(128),
+        }),
+    }
+}
+
+/// Like `row_writer`, but lays the rows out as `shape`. Every shape but
+/// `Shape::Array` is one document built up in memory, so they can't be
+/// written as ndjson. `columns` are the output column names in order, and
+/// `key` the column `Shape::Keyed` indexes rows by.
+pub fn shaped_writer<'a>(
+    shape: Shape,
+    format: OutputFormat,
+    out: Box<dyn Write + 'a>,
+    toml_key: &str,
+    columns: Vec<String>,
+    key: Option<&str>,
+) -> Result<Box<dyn RowWriter + 'a>> {
+    let doc = match shape {
+        Shape::Array => return Ok(row_writer(format, out, toml_key)),
+        _ if matches!(format, OutputFormat::Ndjson) => {
+            anyhow::bail!("--shape {} can't be written as ndjson", shape)
+        }
+        Shape::Keyed => {
+            let Some(key) = key else {
+                anyhow::bail!("--shape keyed needs --key-by");
+            };
+            if !columns.iter().any(|c| c == key) {
+                anyhow::bail!("Unknown column in --key-by: {}", key);
+            }
+            ShapedDoc::Keyed(key.to_string(), Map::new())
+        }
+        Shape::Columnar => ShapedDoc::Columnar(vec![Vec::new(); columns.len()]),
+        Shape::Arrays => ShapedDoc::Arrays(Vec::new()),
+    };
+    Ok(Box::new(ShapedWriter {
+        out,
+        format,
+        columns,
+        doc,
+    }))
+}
+
+enum ShapedDoc {
+    Keyed(String, Map<String, Value>),
+    Columnar(Vec<Vec<Value>>),
+    Arrays(Vec<Value>),
+}
+
+struct ShapedWriter<'a> {
+    out: Box<dyn Write + 'a>,
+    format: OutputFormat,
+    columns: Vec<String>,
+    doc: ShapedDoc,
+}
+
+/// Writes a pretty printed json array one element at a time, laid out the
+/// same as `serde_json::to_string_pretty` on the whole array.
+struct JsonArrayWriter<'a> {
+    out: Box<dyn Write + 'a>,
+    rows: usize,
+}
+
+struct NdjsonWriter<'a> {
+    out: Box<dyn Write + 'a>,
+}
+
+struct BufferedWriter<'a> {
+    out: Box<dyn Write + 'a>,
+    format: OutputFormat,
+    toml_key: String,
+    rows: Vec<Value>,
+}
+
+impl RowWriter for JsonArrayWriter<'_> {
+    fn write_row(&mut self, row: Value) -> Result<()> {
+        self.out
+            .write_all(if self.rows == 0 { b"[\n" } else { b",\n" })?;
+        let content = serde_json::to_string_pretty(&row)?;
+        for (i, line) in content.lines().enumerate() {
+            if i > 0 {
+                self.out.write_all(b"\n")?;
+            }
+            write!(self.out, "  {}", line)?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.out
+            .write_all(if self.rows == 0 { b"[]" } else { b"\n]" })?;
+        Ok(self.out.flush()?)
+    }
+}
+
+impl RowWriter for NdjsonWriter<'_> {
+    fn write_row(&mut self, row: Value) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &row)?;
+        Ok(self.out.write_all(b"\n")?)
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        Ok(self.out.flush()?)
+    }
+}
+
+impl RowWriter for BufferedWriter<'_> {
+    fn write_row(&mut self, row: Value) -> Result<()> {
+        self.rows.push(row);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let content = match self.format {
+            OutputFormat::Yaml => serde_yaml::to_string(&self.rows)?,
+            OutputFormat::Toml => to_toml(self.rows, &self.toml_key)?,
+            OutputFormat::Json => serde_json::to_string_pretty(&self.rows)?,
+            OutputFormat::Ndjson => unreachable!("ndjson is always streamed"),
+        };
+        self.out.write_all(content.as_bytes())?;
+        Ok(self.out.flush()?)
+    }
+}
+
+impl RowWriter for ShapedWriter<'_> {
+    fn write_row(&mut self, row: Value) -> Result<()> {
+        let Value::Object(mut row) = row else {
+            anyhow::bail!("Expected a row object, got {}", row);
+        };
+        match &mut self.doc {
+            ShapedDoc::Keyed(key, rows) => {
+                let id = match row.get(key.as_str()) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Null) | None => anyhow::bail!("Row without a {}: {:?}", key, row),
+                    Some(v) => v.to_string(),
+                };
+                if rows.contains_key(&id) {
+                    anyhow::bail!(
+                        "Duplicate {} {:?}, --shape keyed needs unique keys",
+                        key,
+                        id
+                    );
+                }
+                rows.insert(id, Value::Object(row));
+            }
+            ShapedDoc::Columnar(values) => {
+                for (name, values) in self.columns.iter().zip(values) {
+                    values.push(row.remove(name).unwrap_or(Value::Null));
+                }
+            }
+            ShapedDoc::Arrays(rows) => rows.push(Value::Array(
+                self.columns
+                    .iter()
+                    .map(|name| row.remove(name).unwrap_or(Value::Null))
+                    .collect(),
+            )),
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let toml = matches!(self.format, OutputFormat::Toml);
+        let doc = match self.doc {
+            ShapedDoc::Keyed(_, rows) if toml => {
+                Value::Object(rows.into_iter().map(|(k, v)| (k, strip_nulls(v))).collect())
+            }
+            ShapedDoc::Keyed(_, rows) => Value::Object(rows),
+            ShapedDoc::Columnar(values) => Value::Object(
+                self.columns
+                    .into_iter()
+                    .zip(values.into_iter().map(|column| {
+                        let column = column.into_iter();
+                        Value::Array(if toml {
+                            column.map(blank_null).collect()
+                        } else {
+                            column.collect()
+                        })
+                    }))
+                    .collect(),
+            ),
+            ShapedDoc::Arrays(rows) => {
+                let rows = if toml {
+                    rows.into_iter()
+                        .map(|row| match row {
+                            Value::Array(cells) => cells.into_iter().map(blank_null).collect(),
+                            row => row,
+                        })
+                        .collect()
+                } else {
+                    rows
+                };
+                let mut doc = Map::new();
+                doc.insert("header".to_string(), self.columns.into());
+                doc.insert("rows".to_string(), Value::Array(rows));
+                Value::Object(doc)
+            }
+        };
+        let content = match self.format {
+            OutputFormat::Json => serde_json::to_string_pretty(&doc)?,
+            OutputFormat::Yaml => serde_yaml::to_string(&doc)?,
+            OutputFormat::Toml => toml::to_string(&toml::Value::try_from(doc)?)?,
+            OutputFormat::Ndjson => unreachable!("rejected in shaped_writer"),
+        };
+        self.out.write_all(content.as_bytes())?;
+        Ok(self.out.flush()?)
+    }
+}
+
+/// Leaves null cells out of a row for toml, which has no null, down through
+/// any `--nested` objects. Nulls inside arrays are blanked instead.
+fn strip_nulls(row: Value) -> Value {
+    match row {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, strip_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|v| strip_nulls(blank_null(v)))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+/// Toml has no null, and a cell in an array can't be left out without
+/// shifting the ones after it, so it's written as the empty string the csv
+/// cell was.
+fn blank_null(cell: Value) -> Value {
+    match cell {
+        Value::Null => Value::String(String::new()),
+        v => v,
+    }
+}
+
+/// Toml has no top-level arrays and no null, so rows go under `key` as an
+/// array of tables and null cells are left out.
+fn to_toml(rows: Vec<Value>, key: &str) -> Result<String> {
+    let rows = rows.into_iter().map(strip_nulls).collect::<Vec<_>>();
+    let mut table = toml::Table::new();
+    table.insert(key.to_string(), toml::Value::try_from(rows)?);
+    Ok(toml::to_string(&table)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write_all(format: OutputFormat, rows: &[Value]) -> Result<String> {
+        let mut buf = Vec::new();
+        let mut writer = row_writer(format, Box::new(&mut buf), "players");
+        for row in rows {
+            writer.write_row(row.clone())?;
+        }
+        writer.finish()?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    #[test]
+    fn test_json_array_matches_pretty() -> Result<()> {
+        let rows = vec![
+            json!({"a": 1, "b": {"c": [1, 2]}}),
+            json!({"a": 2, "b": null}),
+        ];
+        assert_eq!(
+            write_all(OutputFormat::Json, &rows)?,
+            serde_json::to_string_pretty(&rows)?
+        );
+        assert_eq!(write_all(OutputFormat::Json, &[])?, "[]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ndjson() -> Result<()> {
+        let rows = vec![json!({"a": 1}), json!({"a": "x\ny"})];
+        assert_eq!(
+            write_all(OutputFormat::Ndjson, &rows)?,
+            "{\"a\":1}\n{\"a\":\"x\\ny\"}\n"
+        );
+        Ok(())
+    }
+
+    fn write_shaped(shape: Shape, key: Option<&str>) -> Result<Value> {
+        let rows = vec![
+            json!({"Name": "Mattia Perin", "Kit Number": 37}),
+            json!({"Name": "Gianluigi Buffon", "Kit Number": 77}),
+        ];
+        let mut buf = Vec::new();
+        let columns = vec!["Name".to_string(), "Kit Number".to_string()];
+        let mut writer = shaped_writer(
+            shape,
+            OutputFormat::Json,
+            Box::new(&mut buf),
+            "rows",
+            columns,
+            key,
+        )?;
+        for row in rows {
+            writer.write_row(row)?;
+        }
+        writer.finish()?;
+        Ok(serde_json::from_slice(&buf)?)
+    }
+
+    #[test]
+    fn test_shapes() -> Result<()> {
+        assert_eq!(
+            write_shaped(Shape::Keyed, Some("Kit Number"))?,
+            json!({
+                "37": {"Name": "Mattia Perin", "Kit Number": 37},
+                "77": {"Name": "Gianluigi Buffon", "Kit Number": 77},
+            })
+        );
+        assert_eq!(
+            write_shaped(Shape::Columnar, None)?,
+            json!({"Name": ["Mattia Perin", "Gianluigi Buffon"], "Kit Number": [37, 77]})
+        );
+        assert_eq!(
+            write_shaped(Shape::Arrays, None)?,
+            json!({
+                "header": ["Name", "Kit Number"],
+                "rows": [["Mattia Perin", 37], ["Gianluigi Buffon", 77]],
+            })
+        );
+        assert!(write_shaped(Shape::Keyed, None).is_err());
+        assert!(write_shaped(Shape::Keyed, Some("Club")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shapes_toml_with_empty_cell() -> Result<()> {
+        let rows = vec![
+            json!({"Name": "Mattia Perin", "Kit Number": 37}),
+            json!({"Name": "Gianluigi Buffon", "Kit Number": null}),
+        ];
+        let columns = vec!["Name".to_string(), "Kit Number".to_string()];
+        let write = |shape: Shape| -> Result<toml::Table> {
+            let mut buf = Vec::new();
+            let mut writer = shaped_writer(
+                shape,
+                OutputFormat::Toml,
+                Box::new(&mut buf),
+                "rows",
+                columns.clone(),
+                Some("Name"),
+            )?;
+            for row in &rows {
+                writer.write_row(row.clone())?;
+            }
+            writer.finish()?;
+            Ok(toml::from_str(std::str::from_utf8(&buf)?)?)
+        };
+        let table = |s: &str| -> Result<toml::Table> { Ok(toml::from_str(s)?) };
+        assert_eq!(
+            write(Shape::Array)?,
+            table(
+                "[[rows]]\nName = \"Mattia Perin\"\n\"Kit Number\" = 37\n\
+                 [[rows]]\nName = \"Gianluigi Buffon\"\n"
+            )?
+        );
+        assert_eq!(
+            write(Shape::Keyed)?,
+            table(
+                "[\"Mattia Perin\"]\nName = \"Mattia Perin\"\n\"Kit Number\" = 37\n\
+                 [\"Gianluigi Buffon\"]\nName = \"Gianluigi Buffon\"\n"
+            )?
+        );
+        assert_eq!(
+            write(Shape::Columnar)?,
+            table(
+                "Name = [\"Mattia Perin\", \"Gianluigi Buffon\"]\n\"Kit Number\" = [37, \"\"]\n"
+            )?
+        );
+        assert_eq!(
+            write(Shape::Arrays)?,
+            table(
+                "header = [\"Name\", \"Kit Number\"]\n\
+                 rows = [[\"Mattia Perin\", 37], [\"Gianluigi Buffon\", \"\"]]\n"
+            )?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_toml() -> Result<()> {
+        let rows = vec![
+            json!({"Name": "Mattia Perin", "Kit Number": 37, "DOB": null}),
+            json!({"Name": "Gianluigi Buffon", "Kit Number": 77, "DOB": null}),
+        ];
+        let content = write_all(OutputFormat::Toml, &rows)?;
+        assert_eq!(content.matches("[[players]]").count(), 2);
+        assert!(content.contains("\"Kit Number\" = 37"));
+        assert!(!content.contains("DOB"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_toml_nested() -> Result<()> {
+        let rows = vec![json!({
+            "Name": "Mattia Perin",
+            "stats": {"goals": null, "caps": 2},
+            "tags": ["keeper", null, {"since": null, "club": "Genoa"}],
+        })];
+        let content = write_all(OutputFormat::Toml, &rows)?;
+        let table: toml::Table = toml::from_str(&content)?;
+        let expected: toml::Table = toml::from_str(
+            "[[players]]\nName = \"Mattia Perin\"\n\
+             tags = [\"keeper\", \"\", { club = \"Genoa\" }]\n\
+             [players.stats]\ncaps = 2\n",
+        )?;
+        assert_eq!(table, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    io::{Cursor, Read},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+use rusqlite::{types::ValueRef, Connection};
+use serde_json::{Map, Number, Value};
+
+use super::{csv_infer::infer_record_types, csv_output::row_writer, CsvDialect};
+use crate::{
+    cli::{ColumnType, OutputFormat},
+    utils::{get_vec, get_writer},
+};
+
+pub(super) fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Opens `input` afresh on every call, for loads that read it twice. Stdin
+/// can't be rewound, so it's read into memory once up front.
+pub(super) fn reopener<'a>(
+    input: &'a str,
+    dialect: &'a CsvDialect,
+) -> Result<impl Fn() -> Result<Reader<Box<dyn Read>>> + 'a> {
+    let buffered: Option<Rc<[u8]>> = if input == "-" {
+        Some(get_vec(input)?.into())
+    } else {
+        None
+    };
+    Ok(move || match &buffered {
+        Some(data) => dialect.decoded(Box::new(Cursor::new(data.clone())) as Box<dyn Read>),
+        None => dialect.open(input),
+    })
+}
+
+/// Loads every record into a new table. Columns are declared with the type
+/// inferred from their cells, so sqlite compares and sorts numbers as
+/// numbers; empty cells in typed columns become NULL. `open` is called
+/// twice, once to infer the types and once to stream the rows in, so
+/// nothing is held in memory. Returns the number of rows loaded. Run it
+/// inside a transaction, sqlite inserts are slow without one.
+pub fn load_table<R: Read>(
+    conn: &Connection,
+    name: &str,
+    headers: &StringRecord,
+    open: impl Fn() -> Result<Reader<R>>,
+) -> Result<usize> {
+    let types = infer_record_types(
+        open()?.into_records().map(|record| Ok(record?)),
+        headers.len(),
+    )?;
+    let columns = headers
+        .iter()
+        .zip(&types)
+        .map(|(header, ty)| {
+            let decl = match ty {
+                ColumnType::Int => "INTEGER",
+                ColumnType::Float => "REAL",
+                _ => "TEXT",
+            };
+            format!("{} {}", quote_ident(header), decl)
+        })
+        .collect::<Vec<_>>();
+    conn.execute(
+        &format!(
+            "CREATE TABLE {} ({})",
+            quote_ident(name),
+            columns.join(", ")
+        ),
+        [],
+    )?;
+    let placeholders = vec!["?"; headers.len()].join(", ");
+    let mut insert = conn.prepare(&format!(
+        "INSERT INTO {} VALUES ({})",
+        quote_ident(name),
+        placeholders
+    ))?;
+    let mut rows = 0;
+    for row in open()?.into_records() {
+        let row = row?;
+        let params = (0..headers.len()).map(|i| {
+            let cell = row.get(i).unwrap_or("");
+            let typed = types[i] != ColumnType::String;
+            (!typed || !cell.trim().is_empty()).then_some(cell)
+        });
+        insert.execute(rusqlite::params_from_iter(params))?;
+        rows += 1;
+    }
+    Ok(rows)
+}
+
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(s) | ValueRef::Blob(s) => Value::String(String::from_utf8_lossy(s).into()),
+    }
+}
+
+/// Runs `sql` and hands every result row to `emit` as a json object keyed by
+/// result column name.
+pub fn run_query(
+    conn: &Connection,
+    sql: &str,
+    mut emit: impl FnMut(Value) -> Result<()>,
+) -> Result<()> {
+    let mut stmt = conn.prepare(sql)?;
+    let names = stmt
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut object = Map::new();
+        for (i, name) in names.iter().enumerate() {
+            object.insert(name.clone(), json_value(row.get_ref(i)?));
+        }
+        emit(Value::Object(object))?;
+    }
+    Ok(())
+}
+
+/// Registers each `(name, path)` csv file, read with the dialect at the same
+/// index of `dialects`, as a table in an in-memory sqlite database and writes
+/// the query result out in `format`.
+pub fn process_csv_query(
+    sql: &str,
+    tables: &[(String, String)],
+    output: &str,
+    format: OutputFormat,
+    toml_key: &str,
+    dialects: &[CsvDialect],
+) -> Result<()> {
+    let mut conn = Connection::open_in_memory()?;
+    let tx = conn.transaction()?;
+    for ((name, path), dialect) in tables.iter().zip(dialects) {
+        let open = reopener(path, dialect)?;
+        let headers = dialect.headers(&mut open()?)?;
+        load_table(&tx, name, &headers, open)?;
+    }
+    tx.commit()?;
+    let mut writer = row_writer(format, get_writer(output)?, toml_key);
+    run_query(&conn, sql, |row| writer.write_row(row))?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(conn: &Connection, name: &str, data: &str) -> Result<()> {
+        let dialect = CsvDialect::default();
+        let open = || Ok(dialect.reader(data.as_bytes()));
+        let headers = dialect.headers(&mut open()?)?;
+        load_table(conn, name, &headers, open)?;
+        Ok(())
+    }
+
+    fn query(conn: &Connection, sql: &str) -> Result<Vec<Value>> {
+        let mut rows = Vec::new();
+        run_query(conn, sql, |row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        Ok(rows)
+    }
+
+    #[test]
+    fn test_group_order_limit() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        let dialect = CsvDialect::default();
+        let open = || dialect.reader_from_path("assets/juventus.csv");
+        let headers = dialect.headers(&mut open()?)?;
+        assert_eq!(load_table(&conn, "players", &headers, open)?, 27);
+        let rows = query(
+            &conn,
+            "SELECT Nationality, COUNT(*) AS n FROM players \
+             WHERE \"Kit Number\" < 100 GROUP BY Nationality ORDER BY 2 DESC LIMIT 1",
+        )?;
+        assert_eq!(rows, vec![json!({"Nationality": "Italy", "n": 8})]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_and_numeric_types() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        load(&conn, "players", "Name,Kit\nDybala,10\nBuffon,77\nKean,\n")?;
+        load(&conn, "caps", "Player,Caps\nDybala,34\nBuffon,176\n")?;
+        let rows = query(
+            &conn,
+            "SELECT p.Name, c.Caps FROM players p JOIN caps c ON c.Player = p.Name \
+             ORDER BY c.Caps DESC",
+        )?;
+        assert_eq!(
+            rows,
+            vec![
+                json!({"Name": "Buffon", "Caps": 176}),
+                json!({"Name": "Dybala", "Caps": 34})
+            ]
+        );
+        let rows = query(&conn, "SELECT Kit FROM players WHERE Name = 'Kean'")?;
+        assert_eq!(rows, vec![json!({"Kit": null})]);
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::Write;
+
+use anyhow::Result;
+use csv::{Position, StringRecord, Writer, WriterBuilder};
+
+use crate::{cli::OnError, utils::get_writer};
+
+/// Collects the rows a conversion couldn't handle. Under `OnError::Fail`
+/// the first one aborts, otherwise they're counted and, for
+/// `OnError::Report`, written out as `line,byte,error,record`.
+pub struct Rejects {
+    mode: OnError,
+    path: String,
+    writer: Option<Writer<Box<dyn Write>>>,
+    delimiter: u8,
+    count: usize,
+}
+
+impl Rejects {
+    pub fn new(mode: OnError, path: &str, delimiter: u8) -> Result<Self> {
+        let writer = match mode {
+            OnError::Report => {
+                let mut writer = WriterBuilder::new().from_writer(get_writer(path)?);
+                writer.write_record(["line", "byte", "error", "record"])?;
+                Some(writer)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            mode,
+            path: path.to_string(),
+            writer,
+            delimiter,
+            count: 0,
+        })
+    }
+
+    /// Deals with a bad row: hands `err` back under `OnError::Fail`,
+    /// otherwise records it and lets the caller carry on. `record` is the
+    /// row as parsed, when it got that far.
+    pub fn reject(
+        &mut self,
+        err: anyhow::Error,
+        position: Option<&Position>,
+        record: Option<&StringRecord>,
+    ) -> Result<()> {
+        if self.mode == OnError::Fail {
+            return Err(err);
+        }
+        self.count += 1;
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        let raw = match record {
+            Some(record) => {
+                let mut raw = WriterBuilder::new()
+                    .delimiter(self.delimiter)
+                    .from_writer(Vec::new());
+                raw.write_record(record)?;
+                let raw = raw.into_inner().map_err(|e| e.into_error())?;
+                String::from_utf8_lossy(&raw).trim_end().to_string()
+            }
+            None => String::new(),
+        };
+        writer.write_record([
+            position.map_or(String::new(), |p| p.line().to_string()),
+            position.map_or(String::new(), |p| p.byte().to_string()),
+            err.to_string(),
+            raw,
+        ])?;
+        Ok(())
+    }
+
+    /// Flushes the report and prints how many rows were left out.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            writer.flush()?;
+        }
+        match self.mode {
+            _ if self.count == 0 => {}
+            OnError::Report => eprintln!("Rejected {} rows, see {}", self.count, self.path),
+            _ => eprintln!("Skipped {} rows", self.count),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::CsvDialect;
+
+    #[test]
+    fn test_fail_returns_error() {
+        let mut rejects = Rejects::new(OnError::Fail, "-", b',').expect("no file to open");
+        assert!(rejects
+            .reject(anyhow::anyhow!("bad row"), None, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_report_bad_rows() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("rcli-rejects-{}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let mut rejects = Rejects::new(OnError::Report, &path, b',')?;
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader(&b"a,b\n1,2\n3\n4,x\n"[..]);
+        for result in reader.records() {
+            match result {
+                Ok(record) if &record[1] == "x" => rejects.reject(
+                    anyhow::anyhow!("not a number"),
+                    record.position(),
+                    Some(&record),
+                )?,
+                Ok(_) => {}
+                Err(e) => {
+                    let position = e.position().cloned();
+                    rejects.reject(e.into(), position.as_ref(), None)?
+                }
+            }
+        }
+        assert_eq!(rejects.count, 2);
+        rejects.finish()?;
+        let report = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let lines = report.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "line,byte,error,record");
+        assert!(lines[1].starts_with("3,8,"));
+        assert_eq!(lines[2], "4,10,not a number,\"4,x\"");
+        Ok(())
+    }
+}
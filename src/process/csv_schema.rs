@@ -0,0 +1,307 @@
+use std::{collections::BTreeSet, fs, io::Read};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+use jsonschema::{paths::PathChunk, JSONSchema};
+use serde_json::{json, Map, Value};
+
+use super::{
+    csv_infer::{infer_cell, merge_type},
+    CsvDialect,
+};
+use crate::cli::ColumnType;
+
+const SCHEMA_DRAFT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// A cell that failed validation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub line: u64,
+    pub column: String,
+    pub message: String,
+}
+
+/// What one pass over a column learns about it.
+#[derive(Debug, Default)]
+struct ColumnProfile {
+    ty: Option<ColumnType>,
+    nullable: bool,
+    /// Distinct values, until there are too many for an enum.
+    values: Option<BTreeSet<String>>,
+    /// The common shape of every value, until two values disagree.
+    shape: Option<Option<String>>,
+    rows: usize,
+}
+
+impl ColumnProfile {
+    fn new() -> Self {
+        Self {
+            values: Some(BTreeSet::new()),
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, cell: &str, max_enum: usize) {
+        self.rows += 1;
+        let ty = infer_cell(cell);
+        if ty.is_none() {
+            self.nullable = true;
+            return;
+        }
+        self.ty = merge_type(self.ty, ty);
+        if let Some(values) = &mut self.values {
+            values.insert(cell.to_string());
+            if values.len() > max_enum {
+                self.values = None;
+            }
+        }
+        let shape = shape_of(cell);
+        self.shape = match self.shape.take() {
+            None => Some(Some(shape)),
+            Some(Some(s)) if s == shape => Some(Some(s)),
+            Some(_) => Some(None),
+        };
+    }
+
+    fn schema(self) -> Value {
+        let ty = match self.ty.unwrap_or(ColumnType::String) {
+            ColumnType::String => "string",
+            ColumnType::Int => "integer",
+            ColumnType::Float => "number",
+            ColumnType::Bool => "boolean",
+        };
+        let mut schema = Map::new();
+        schema.insert(
+            "type".to_string(),
+            if self.nullable {
+                json!([ty, "null"])
+            } else {
+                json!(ty)
+            },
+        );
+        if ty != "string" {
+            return Value::Object(schema);
+        }
+        match self.values {
+            // only worth an enum when values actually repeat
+            Some(values) if !values.is_empty() && values.len() * 2 <= self.rows => {
+                let mut values = values.into_iter().map(Value::String).collect::<Vec<_>>();
+                if self.nullable {
+                    values.push(Value::Null);
+                }
+                schema.insert("enum".to_string(), Value::Array(values));
+            }
+            _ => {
+                if let Some(Some(shape)) = self.shape {
+                    schema.insert("pattern".to_string(), Value::String(shape));
+                }
+            }
+        }
+        Value::Object(schema)
+    }
+}
+
+/// Generalizes a value into an anchored regex: runs of ascii letters become
+/// `[A-Za-z]+`, runs of digits `\d+`, everything else is kept literally.
+fn shape_of(cell: &str) -> String {
+    let mut shape = String::from("^");
+    let mut last = None;
+    for c in cell.chars() {
+        let class = if c.is_ascii_alphabetic() {
+            Some("[A-Za-z]+")
+        } else if c.is_ascii_digit() {
+            Some("\\d+")
+        } else {
+            None
+        };
+        match class {
+            Some(class) if last == Some(class) => continue,
+            Some(class) => shape.push_str(class),
+            None => shape.push_str(&regex::escape(&c.to_string())),
+        }
+        last = class;
+    }
+    shape.push('$');
+    shape
+}
+
+pub fn infer_schema<R: Read>(
+    reader: &mut Reader<R>,
+    headers: &StringRecord,
+    max_enum: usize,
+) -> Result<Value> {
+    let mut profiles = headers
+        .iter()
+        .map(|_| ColumnProfile::new())
+        .collect::<Vec<_>>();
+    for record in reader.records() {
+        let record = record?;
+        for (profile, cell) in profiles.iter_mut().zip(record.iter()) {
+            profile.add(cell, max_enum);
+        }
+    }
+    let properties = headers
+        .iter()
+        .zip(profiles)
+        .map(|(name, profile)| (name.to_string(), profile.schema()))
+        .collect::<Map<_, _>>();
+    Ok(json!({
+        "$schema": SCHEMA_DRAFT,
+        "type": "object",
+        "properties": properties,
+        "required": headers.iter().collect::<Vec<_>>(),
+    }))
+}
+
+pub fn process_csv_schema(input: &str, dialect: &CsvDialect, max_enum: usize) -> Result<Value> {
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    infer_schema(&mut reader, &headers, max_enum)
+}
+
+/// Types a cell by what the schema allows for its column, so `"7"` is checked
+/// as `7` against an integer column. Cells that fit none of the types stay
+/// strings and fail validation.
+fn cell_value(cell: &str, schema: Option<&Value>) -> Value {
+    let allows = |ty: &str| match schema.and_then(|s| s.get("type")) {
+        Some(Value::String(t)) => t == ty,
+        Some(Value::Array(types)) => types.iter().any(|t| t == ty),
+        _ => false,
+    };
+    let trimmed = cell.trim();
+    if trimmed.is_empty() {
+        return if allows("string") && !allows("null") {
+            Value::String(cell.to_string())
+        } else {
+            Value::Null
+        };
+    }
+    if allows("integer") {
+        if let Ok(v) = trimmed.parse::<i64>() {
+            return json!(v);
+        }
+    }
+    if allows("number") {
+        if let Some(v) = trimmed
+            .parse::<f64>()
+            .ok()
+            .and_then(serde_json::Number::from_f64)
+        {
+            return Value::Number(v);
+        }
+    }
+    if allows("boolean") {
+        match trimmed.to_lowercase().as_str() {
+            "true" => return Value::Bool(true),
+            "false" => return Value::Bool(false),
+            _ => {}
+        }
+    }
+    Value::String(cell.to_string())
+}
+
+pub fn validate_records<R: Read>(
+    reader: &mut Reader<R>,
+    headers: &StringRecord,
+    schema: &Value,
+) -> Result<Vec<Violation>> {
+    let compiled =
+        JSONSchema::compile(schema).map_err(|e| anyhow::anyhow!("Invalid schema: {}", e))?;
+    let properties = schema.get("properties");
+    let mut violations = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        let row = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(name, cell)| {
+                let value = cell_value(cell, properties.and_then(|p| p.get(name)));
+                (name.to_string(), value)
+            })
+            .collect::<Map<_, _>>();
+        let row = Value::Object(row);
+        let errors = match compiled.validate(&row) {
+            Ok(()) => continue,
+            Err(errors) => errors,
+        };
+        for error in errors {
+            let column = match (&error.instance_path).into_iter().next() {
+                Some(PathChunk::Property(name)) => name.to_string(),
+                _ => String::new(),
+            };
+            violations.push(Violation {
+                line,
+                column,
+                message: error.to_string(),
+            });
+        }
+    }
+    Ok(violations)
+}
+
+pub fn process_csv_validate(
+    input: &str,
+    dialect: &CsvDialect,
+    schema: &str,
+) -> Result<Vec<Violation>> {
+    let schema: Value = serde_json::from_str(&fs::read_to_string(schema)?)?;
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    validate_records(&mut reader, &headers, &schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape_of() {
+        assert_eq!(
+            shape_of("Apr 18, 1990 (29)"),
+            r"^[A-Za-z]+ \d+, \d+ \(\d+\)$"
+        );
+        assert_eq!(shape_of("A-12"), r"^[A-Za-z]+\-\d+$");
+    }
+
+    #[test]
+    fn test_infer_juventus_schema() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let headers = dialect.headers(&mut reader)?;
+        let schema = infer_schema(&mut reader, &headers, 10)?;
+        let properties = &schema["properties"];
+        assert_eq!(properties["Kit Number"], json!({"type": "integer"}));
+        assert_eq!(
+            properties["DOB"]["pattern"],
+            json!(r"^[A-Za-z]+ \d+, \d+ \(\d+\)$")
+        );
+        assert!(properties["Position"]["enum"]
+            .as_array()
+            .is_some_and(|v| v.contains(&json!("Goalkeeper"))));
+        assert!(properties["Name"].get("enum").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_reports_cells() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let data = "Name,Kit Number\nMattia Perin,37\nBuffon,x\n,1\n";
+        let mut reader = dialect.reader(data.as_bytes());
+        let headers = dialect.headers(&mut reader)?;
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "Name": {"type": "string", "minLength": 1},
+                "Kit Number": {"type": "integer"}
+            }
+        });
+        let violations = validate_records(&mut reader, &headers, &schema)?;
+        let found = violations
+            .iter()
+            .map(|v| (v.line, v.column.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(3, "Kit Number"), (4, "Name")]);
+        Ok(())
+    }
+}
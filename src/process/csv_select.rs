@@ -0,0 +1,81 @@
+use anyhow::Result;
+use csv::StringRecord;
+
+/// The columns that make it into the output, in output order, with the name
+/// each one is written under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    columns: Vec<(usize, String)>,
+}
+
+impl Projection {
+    /// Keeps the `select`ed columns (all of them when empty) and renames them
+    /// per `rename`. Both refer to the columns by their original names.
+    pub fn new(
+        headers: &StringRecord,
+        select: &[String],
+        rename: &[(String, String)],
+    ) -> Result<Self> {
+        let position = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown column: {}", name))
+        };
+        let mut columns = if select.is_empty() {
+            Self::all(headers).columns
+        } else {
+            select
+                .iter()
+                .map(|name| Ok((position(name)?, name.clone())))
+                .collect::<Result<Vec<_>>>()?
+        };
+        for (from, to) in rename {
+            let idx = position(from)?;
+            for (_, name) in columns.iter_mut().filter(|(i, _)| *i == idx) {
+                *name = to.clone();
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    pub fn all(headers: &StringRecord) -> Self {
+        Self {
+            columns: headers
+                .iter()
+                .enumerate()
+                .map(|(i, h)| (i, h.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Pairs of (index in the input record, output name).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.columns.iter().map(|(i, name)| (*i, name.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projection() -> Result<()> {
+        let headers = StringRecord::from(vec!["Name", "Position", "Kit Number"]);
+        let projection = Projection::new(
+            &headers,
+            &["Kit Number".to_string(), "Name".to_string()],
+            &[("Kit Number".to_string(), "kit".to_string())],
+        )?;
+        assert_eq!(
+            projection.iter().collect::<Vec<_>>(),
+            vec![(2, "kit"), (0, "Name")]
+        );
+        assert_eq!(
+            Projection::new(&headers, &[], &[])?,
+            Projection::all(&headers)
+        );
+        assert!(Projection::new(&headers, &["Club".to_string()], &[]).is_err());
+        Ok(())
+    }
+}
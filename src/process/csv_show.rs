@@ -0,0 +1,272 @@
+use std::{
+    collections::VecDeque,
+    io::{IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::Result;
+use csv::StringRecord;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::{
+    csv_infer::{infer_cell, merge_type},
+    CsvDialect,
+};
+use crate::cli::{ColumnType, ShowStyle};
+
+/// Which rows of the file to show.
+#[derive(Debug, Clone, Copy)]
+pub enum RowWindow {
+    All,
+    Head(usize),
+    Tail(usize),
+}
+
+pub fn process_csv_show(
+    input: &str,
+    dialect: &CsvDialect,
+    window: RowWindow,
+    style: ShowStyle,
+    max_width: usize,
+    pager: bool,
+) -> Result<()> {
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    let records = reader.records();
+    let rows = match window {
+        RowWindow::All => records.collect::<Result<Vec<_>, _>>()?,
+        RowWindow::Head(n) => records.take(n).collect::<Result<Vec<_>, _>>()?,
+        RowWindow::Tail(n) => {
+            let mut tail = VecDeque::with_capacity(n + 1);
+            for record in records {
+                tail.push_back(record?);
+                if tail.len() > n {
+                    tail.pop_front();
+                }
+            }
+            tail.into()
+        }
+    };
+
+    let content = match style {
+        ShowStyle::Table => render_table(&headers, &rows, max_width),
+        ShowStyle::Markdown => render_markdown(&headers, &rows),
+        ShowStyle::Html => render_html(&headers, &rows),
+    };
+    if pager && style == ShowStyle::Table && std::io::stdout().is_terminal() {
+        page(&content)
+    } else {
+        Ok(std::io::stdout().write_all(content.as_bytes())?)
+    }
+}
+
+/// Hands the output to `$PAGER` (or `less`), falling back to plain stdout
+/// when no pager can be started.
+fn page(content: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -FRSX".to_string());
+    let mut parts = pager.split_whitespace();
+    let child = parts.next().and_then(|cmd| {
+        Command::new(cmd)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .spawn()
+            .ok()
+    });
+    let Some(mut child) = child else {
+        return Ok(std::io::stdout().write_all(content.as_bytes())?);
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // the user may quit the pager before reading everything
+        let _ = stdin.write_all(content.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Cuts a cell down to `max_width` terminal columns, counting wide CJK and
+/// emoji characters as two.
+fn truncate(cell: &str, max_width: usize) -> String {
+    let cell = cell.replace(['\r', '\n'], " ");
+    if max_width == 0 || cell.width() <= max_width {
+        return cell;
+    }
+    let mut cut = String::new();
+    let mut width = 0;
+    for c in cell.chars() {
+        width += c.width().unwrap_or(0);
+        if width >= max_width {
+            break;
+        }
+        cut.push(c);
+    }
+    cut.push('…');
+    cut
+}
+
+/// Draws a box table. Columns where every cell is a number are right aligned.
+pub fn render_table(headers: &StringRecord, rows: &[StringRecord], max_width: usize) -> String {
+    let cells = |record: &StringRecord| {
+        (0..headers.len())
+            .map(|i| truncate(record.get(i).unwrap_or(""), max_width))
+            .collect::<Vec<_>>()
+    };
+    let header = cells(headers);
+    let body = rows.iter().map(cells).collect::<Vec<_>>();
+
+    let widths = (0..headers.len())
+        .map(|i| {
+            body.iter()
+                .chain(std::iter::once(&header))
+                .map(|row| row[i].width())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    let numeric = (0..headers.len())
+        .map(|i| {
+            let ty = rows.iter().fold(None, |ty, row| {
+                merge_type(ty, infer_cell(row.get(i).unwrap_or("")))
+            });
+            matches!(ty, Some(ColumnType::Int | ColumnType::Float))
+        })
+        .collect::<Vec<_>>();
+
+    let rule = |left: &str, mid: &str, right: &str| {
+        let segments = widths.iter().map(|w| "─".repeat(w + 2)).collect::<Vec<_>>();
+        format!("{}{}{}\n", left, segments.join(mid), right)
+    };
+    let line = |row: &[String], align: &[bool]| {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .zip(align)
+            .map(|((cell, w), right)| {
+                // padded by hand, since `format!` counts chars, not columns
+                let pad = " ".repeat(w - cell.width());
+                if *right {
+                    format!(" {}{} ", pad, cell)
+                } else {
+                    format!(" {}{} ", cell, pad)
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("│{}│\n", cells.join("│"))
+    };
+
+    let mut out = rule("┌", "┬", "┐");
+    out.push_str(&line(&header, &vec![false; headers.len()]));
+    out.push_str(&rule("├", "┼", "┤"));
+    for row in &body {
+        out.push_str(&line(row, &numeric));
+    }
+    out.push_str(&rule("└", "┴", "┘"));
+    out
+}
+
+pub fn render_markdown(headers: &StringRecord, rows: &[StringRecord]) -> String {
+    let escape = |cell: &str| {
+        cell.replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace('\n', "<br>")
+    };
+    let line = |record: &StringRecord| {
+        let cells = (0..headers.len())
+            .map(|i| escape(record.get(i).unwrap_or("")))
+            .collect::<Vec<_>>();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let mut out = line(headers);
+    out.push_str(&format!("|{}\n", " --- |".repeat(headers.len())));
+    for row in rows {
+        out.push_str(&line(row));
+    }
+    out
+}
+
+pub fn render_html(headers: &StringRecord, rows: &[StringRecord]) -> String {
+    let escape = |cell: &str| {
+        cell.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let line = |record: &StringRecord, tag: &str| {
+        let cells = (0..headers.len())
+            .map(|i| format!("<{tag}>{}</{tag}>", escape(record.get(i).unwrap_or(""))))
+            .collect::<String>();
+        format!("    <tr>{}</tr>\n", cells)
+    };
+    let mut out = String::from("<table>\n  <thead>\n");
+    out.push_str(&line(headers, "th"));
+    out.push_str("  </thead>\n  <tbody>\n");
+    for row in rows {
+        out.push_str(&line(row, "td"));
+    }
+    out.push_str("  </tbody>\n</table>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (StringRecord, Vec<StringRecord>) {
+        (
+            StringRecord::from(vec!["Name", "Kit"]),
+            vec![
+                StringRecord::from(vec!["Gianluigi Buffon", "77"]),
+                StringRecord::from(vec!["<Szczesny> | W", "1"]),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_render_table() {
+        let (headers, rows) = sample();
+        let table = render_table(&headers, &rows, 10);
+        assert_eq!(
+            table,
+            "┌────────────┬─────┐\n\
+             │ Name       │ Kit │\n\
+             ├────────────┼─────┤\n\
+             │ Gianluigi… │  77 │\n\
+             │ <Szczesny… │   1 │\n\
+             └────────────┴─────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table_wide_chars() {
+        let headers = StringRecord::from(vec!["Name", "Kit"]);
+        let rows = vec![
+            StringRecord::from(vec!["武藤嘉紀", "9"]),
+            StringRecord::from(vec!["⚽ Dybala", "10"]),
+        ];
+        assert_eq!(
+            render_table(&headers, &rows, 7),
+            "┌─────────┬─────┐\n\
+             │ Name    │ Kit │\n\
+             ├─────────┼─────┤\n\
+             │ 武藤嘉… │   9 │\n\
+             │ ⚽ Dyb… │  10 │\n\
+             └─────────┴─────┘\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let (headers, rows) = sample();
+        assert_eq!(
+            render_markdown(&headers, &rows),
+            "| Name | Kit |\n| --- | --- |\n| Gianluigi Buffon | 77 |\n| <Szczesny> \\| W | 1 |\n"
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        let (headers, rows) = sample();
+        let html = render_html(&headers, &rows);
+        assert!(html.contains("<tr><th>Name</th><th>Kit</th></tr>"));
+        assert!(html.contains("<td>&lt;Szczesny&gt; | W</td>"));
+    }
+}
@@ -0,0 +1,444 @@
+use std::{
+    cell::{Ref, RefCell},
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord, Writer, WriterBuilder};
+
+use super::CsvDialect;
+use crate::utils::get_writer;
+
+/// Most parts kept open at once when splitting by column. Past that, the
+/// part written to longest ago is closed and reopened if its value comes back,
+/// so a column with many values doesn't run out of file handles.
+const MAX_OPEN_PARTS: usize = 256;
+
+/// How `split` decides where one part ends.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitBy {
+    /// At most this many rows per part.
+    Rows(usize),
+    /// At most this many bytes per part, header included. A part always
+    /// takes at least one row, even if that row alone is over the limit.
+    Bytes(u64),
+    /// One part per distinct value of the column.
+    Column(String),
+}
+
+struct Part {
+    file: BufWriter<File>,
+    rows: usize,
+    bytes: u64,
+}
+
+impl Part {
+    fn create(path: &Path, header: &[u8]) -> Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(header)?;
+        Ok(Self {
+            file,
+            rows: 0,
+            bytes: header.len() as u64,
+        })
+    }
+
+    /// Reopens a part closed earlier to add more rows to it.
+    fn reopen(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            rows: 0,
+            bytes: 0,
+        })
+    }
+
+    fn push(&mut self, line: &[u8]) -> Result<()> {
+        self.file.write_all(line)?;
+        self.rows += 1;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// The buffer an `Encoder` writes into, shared so the line can be read back
+/// while the csv writer still owns it.
+#[derive(Clone, Default)]
+struct LineBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for LineBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes one record at a time, so a row's size is known before picking
+/// its part. The writer and its buffer are reused for every row.
+struct Encoder {
+    writer: Writer<LineBuf>,
+    line: LineBuf,
+}
+
+impl Encoder {
+    fn new(dialect: &CsvDialect) -> Self {
+        let line = LineBuf::default();
+        let writer = WriterBuilder::new()
+            .delimiter(dialect.delimiter)
+            .quote(dialect.quote)
+            .flexible(true)
+            .from_writer(line.clone());
+        Self { writer, line }
+    }
+
+    fn encode(&mut self, record: &StringRecord) -> Result<Ref<'_, Vec<u8>>> {
+        self.line.0.borrow_mut().clear();
+        self.writer.write_record(record)?;
+        self.writer.flush()?;
+        Ok(self.line.0.borrow())
+    }
+}
+
+/// A column value made safe to use in a file name.
+fn value_stem(value: &str) -> String {
+    if value.is_empty() {
+        return "empty".to_string();
+    }
+    value
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A file name stem for `value` that no other value has taken. Values that
+/// clean up to the same stem, like `a/b` and `a_b`, or that only differ in
+/// case, get a `_2`, `_3`.. suffix.
+fn unique_stem(value: &str, taken: &mut HashSet<String>) -> String {
+    let base = value_stem(value);
+    let mut stem = base.clone();
+    let mut n = 1;
+    while !taken.insert(stem.to_lowercase()) {
+        n += 1;
+        stem = format!("{}_{}", base, n);
+    }
+    stem
+}
+
+/// Writes the rows of `reader` into parts named `<prefix>_<n>.<ext>`, or
+/// `<prefix>_<value>.<ext>` when splitting by column, each starting with the
+/// header row. Returns the files written, in the order they were started.
+pub fn split<R: Read>(
+    reader: &mut Reader<R>,
+    headers: &StringRecord,
+    by: &SplitBy,
+    dialect: &CsvDialect,
+    out_dir: &Path,
+    prefix: &str,
+    ext: &str,
+) -> Result<Vec<PathBuf>> {
+    let mut encoder = Encoder::new(dialect);
+    let header = if dialect.has_headers {
+        encoder.encode(headers)?.clone()
+    } else {
+        Vec::new()
+    };
+    let column = match by {
+        SplitBy::Column(name) => Some(
+            headers
+                .iter()
+                .position(|h| h == name)
+                .ok_or_else(|| anyhow::anyhow!("Column {} not found", name))?,
+        ),
+        _ => None,
+    };
+    fs::create_dir_all(out_dir)?;
+
+    let mut paths = Vec::new();
+    let mut current: Option<Part> = None;
+    // value -> its part's path, and the parts currently open with when they
+    // were last written to
+    let mut value_paths: HashMap<String, PathBuf> = HashMap::new();
+    let mut stems = HashSet::new();
+    let mut by_value: HashMap<String, (Part, u64)> = HashMap::new();
+    for (n, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = encoder.encode(&record)?;
+        let part = match (by, column) {
+            (_, Some(i)) => {
+                let value = record.get(i).unwrap_or("");
+                if !by_value.contains_key(value) {
+                    if by_value.len() >= MAX_OPEN_PARTS {
+                        let oldest = by_value
+                            .iter()
+                            .min_by_key(|(_, (_, used))| *used)
+                            .map(|(value, _)| value.clone());
+                        if let Some((mut part, _)) = oldest.and_then(|v| by_value.remove(&v)) {
+                            part.file.flush()?;
+                        }
+                    }
+                    let part = match value_paths.get(value) {
+                        Some(path) => Part::reopen(path)?,
+                        None => {
+                            let stem = unique_stem(value, &mut stems);
+                            let path = out_dir.join(format!("{}_{}.{}", prefix, stem, ext));
+                            let part = Part::create(&path, &header)?;
+                            value_paths.insert(value.to_string(), path.clone());
+                            paths.push(path);
+                            part
+                        }
+                    };
+                    by_value.insert(value.to_string(), (part, 0));
+                }
+                let (part, used) = by_value.get_mut(value).expect("part was just opened");
+                *used = n as u64;
+                part
+            }
+            (by, None) => {
+                let full = current.as_ref().is_some_and(|part| match by {
+                    SplitBy::Rows(n) => part.rows >= *n,
+                    SplitBy::Bytes(n) => part.rows > 0 && part.bytes + line.len() as u64 > *n,
+                    SplitBy::Column(_) => false,
+                });
+                if full || current.is_none() {
+                    if let Some(mut part) = current.take() {
+                        part.file.flush()?;
+                    }
+                    let path = out_dir.join(format!("{}_{:03}.{}", prefix, paths.len() + 1, ext));
+                    current = Some(Part::create(&path, &header)?);
+                    paths.push(path);
+                }
+                current.as_mut().expect("part was just created")
+            }
+        };
+        part.push(&line)?;
+    }
+    for part in current
+        .iter_mut()
+        .chain(by_value.values_mut().map(|(part, _)| part))
+    {
+        part.file.flush()?;
+    }
+    Ok(paths)
+}
+
+pub fn process_csv_split(
+    input: &str,
+    by: &SplitBy,
+    out_dir: &str,
+    prefix: Option<&str>,
+    dialect: &CsvDialect,
+) -> Result<()> {
+    let path = Path::new(input);
+    let prefix = match prefix {
+        Some(prefix) => prefix,
+        None if input == "-" => "part",
+        None => path.file_stem().and_then(|s| s.to_str()).unwrap_or("part"),
+    };
+    let ext = match path.extension().and_then(|s| s.to_str()) {
+        Some(ext) if input != "-" => ext,
+        _ => "csv",
+    };
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    let paths = split(
+        &mut reader,
+        &headers,
+        by,
+        dialect,
+        Path::new(out_dir),
+        prefix,
+        ext,
+    )?;
+    for path in paths {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// All columns of all files, in the order they first appear.
+pub fn merge_headers(headers: &[StringRecord]) -> StringRecord {
+    let mut merged: Vec<&str> = Vec::new();
+    for name in headers.iter().flatten() {
+        if !merged.contains(&name) {
+            merged.push(name);
+        }
+    }
+    merged.into_iter().collect()
+}
+
+/// Concatenates files under the union of their columns. Each input is read
+/// with the dialect at the same index of `dialects`, and the result written
+/// in the first one. Cells for columns a file doesn't have are left empty.
+pub fn process_csv_merge(inputs: &[String], output: &str, dialects: &[CsvDialect]) -> Result<()> {
+    let Some(dialect) = dialects.first() else {
+        anyhow::bail!("Nothing to merge");
+    };
+    let mut readers = inputs
+        .iter()
+        .zip(dialects)
+        .map(|(input, dialect)| dialect.open(input))
+        .collect::<Result<Vec<_>>>()?;
+    let headers = readers
+        .iter_mut()
+        .zip(dialects)
+        .map(|(reader, dialect)| dialect.headers(reader))
+        .collect::<Result<Vec<_>>>()?;
+    let merged = merge_headers(&headers);
+
+    let mut writer = WriterBuilder::new()
+        .delimiter(dialect.delimiter)
+        .quote(dialect.quote)
+        .from_writer(get_writer(output)?);
+    if dialect.has_headers {
+        writer.write_record(&merged)?;
+    }
+    for (reader, headers) in readers.iter_mut().zip(&headers) {
+        let columns = merged
+            .iter()
+            .map(|name| headers.iter().position(|h| h == name))
+            .collect::<Vec<_>>();
+        for record in reader.records() {
+            let record = record?;
+            writer.write_record(
+                columns
+                    .iter()
+                    .map(|i| i.and_then(|i| record.get(i)).unwrap_or("")),
+            )?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "Name,Position\nBuffon,Goalkeeper\nDybala,Forward\nKean,Forward\n";
+
+    fn run(by: SplitBy, dir: &str) -> Result<Vec<(String, String)>> {
+        run_data(DATA, by, dir)
+    }
+
+    fn run_data(data: &str, by: SplitBy, dir: &str) -> Result<Vec<(String, String)>> {
+        let out_dir = std::env::temp_dir().join(format!("rcli-{}-{}", dir, std::process::id()));
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader(data.as_bytes());
+        let headers = dialect.headers(&mut reader)?;
+        let paths = split(
+            &mut reader,
+            &headers,
+            &by,
+            &dialect,
+            &out_dir,
+            "players",
+            "csv",
+        )?;
+        let parts = paths
+            .iter()
+            .map(|path| {
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Ok((name, fs::read_to_string(path)?))
+            })
+            .collect::<Result<Vec<_>>>();
+        fs::remove_dir_all(&out_dir)?;
+        parts
+    }
+
+    #[test]
+    fn test_split_rows_and_bytes() -> Result<()> {
+        let parts = run(SplitBy::Rows(2), "split-rows")?;
+        assert_eq!(
+            parts,
+            vec![
+                (
+                    "players_001.csv".to_string(),
+                    "Name,Position\nBuffon,Goalkeeper\nDybala,Forward\n".to_string()
+                ),
+                (
+                    "players_002.csv".to_string(),
+                    "Name,Position\nKean,Forward\n".to_string()
+                ),
+            ]
+        );
+        // header (14) + Buffon (18) fits in 45, adding Dybala (15) doesn't
+        let parts = run(SplitBy::Bytes(45), "split-bytes")?;
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].1, "Name,Position\nDybala,Forward\nKean,Forward\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_by_column() -> Result<()> {
+        let parts = run(SplitBy::Column("Position".into()), "split-column")?;
+        assert_eq!(parts[0].0, "players_Goalkeeper.csv");
+        assert_eq!(parts[1].0, "players_Forward.csv");
+        assert_eq!(parts[1].1, "Name,Position\nDybala,Forward\nKean,Forward\n");
+        assert!(run(SplitBy::Column("Club".into()), "split-missing").is_err());
+
+        let parts = run_data(
+            "Name,Club\nA,a/b\nB,a_b\nC,A_B\nD,a/b\n",
+            SplitBy::Column("Club".into()),
+            "split-clash",
+        )?;
+        let names = parts
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["players_a_b.csv", "players_a_b_2.csv", "players_A_B_3.csv"]
+        );
+        assert_eq!(parts[0].1, "Name,Club\nA,a/b\nD,a/b\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_many_values() -> Result<()> {
+        // twice round more values than there are open parts, so every part
+        // is closed and reopened
+        let values = MAX_OPEN_PARTS + 10;
+        let mut data = "Name,Kit\n".to_string();
+        for round in 0..2 {
+            for kit in 0..values {
+                data.push_str(&format!("p{},{}\n", round, kit));
+            }
+        }
+        let parts = run_data(&data, SplitBy::Column("Kit".into()), "split-many")?;
+        assert_eq!(parts.len(), values);
+        assert_eq!(
+            parts[0],
+            ("players_0.csv".into(), "Name,Kit\np0,0\np1,0\n".into())
+        );
+        assert!(parts
+            .iter()
+            .all(|(_, content)| content.lines().count() == 3));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_headers() {
+        let merged = merge_headers(&[
+            StringRecord::from(vec!["Name", "Position"]),
+            StringRecord::from(vec!["Name", "Club", "Position"]),
+        ]);
+        assert_eq!(merged, StringRecord::from(vec!["Name", "Position", "Club"]));
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+use rusqlite::Connection;
+
+use super::{
+    csv_query::{load_table, quote_ident, reopener},
+    CsvDialect,
+};
+
+/// Where and how `process_csv_to_sqlite` writes the table.
+#[derive(Debug, Default, Clone)]
+pub struct SqliteOptions {
+    pub table: String,
+    /// Columns to create an index on, one index each.
+    pub indexes: Vec<String>,
+    /// Drop an existing table of the same name first, instead of failing.
+    pub replace: bool,
+}
+
+/// Creates `opts.table` in `conn` from the csv rows, then its indexes, all
+/// in one transaction. Returns the number of rows written.
+pub fn write_table(
+    conn: &mut Connection,
+    input: &str,
+    dialect: &CsvDialect,
+    opts: &SqliteOptions,
+) -> Result<usize> {
+    let open = reopener(input, dialect)?;
+    let headers = dialect.headers(&mut open()?)?;
+    if let Some(column) = opts
+        .indexes
+        .iter()
+        .find(|c| !headers.iter().any(|h| h == *c))
+    {
+        anyhow::bail!("Unknown column to index: {}", column);
+    }
+    let tx = conn.transaction()?;
+    if opts.replace {
+        tx.execute(
+            &format!("DROP TABLE IF EXISTS {}", quote_ident(&opts.table)),
+            [],
+        )?;
+    }
+    let rows = load_table(&tx, &opts.table, &headers, open)?;
+    for column in &opts.indexes {
+        tx.execute(
+            &format!(
+                "CREATE INDEX {} ON {} ({})",
+                quote_ident(&format!("{}_{}_idx", opts.table, column)),
+                quote_ident(&opts.table),
+                quote_ident(column)
+            ),
+            [],
+        )?;
+    }
+    tx.commit()?;
+    Ok(rows)
+}
+
+pub fn process_csv_to_sqlite(
+    input: &str,
+    database: &str,
+    dialect: &CsvDialect,
+    opts: &SqliteOptions,
+) -> Result<()> {
+    let mut conn = Connection::open(database)?;
+    let rows = write_table(&mut conn, input, dialect, opts)?;
+    eprintln!(
+        "Wrote {} rows to table {} in {}",
+        rows, opts.table, database
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_table_with_index() -> Result<()> {
+        let mut conn = Connection::open_in_memory()?;
+        let opts = SqliteOptions {
+            table: "players".to_string(),
+            indexes: vec!["Nationality".to_string()],
+            replace: false,
+        };
+        let dialect = CsvDialect::default();
+        assert_eq!(
+            write_table(&mut conn, "assets/juventus.csv", &dialect, &opts)?,
+            27
+        );
+        let (kit, ty): (i64, String) = conn.query_row(
+            "SELECT \"Kit Number\", typeof(\"Kit Number\") FROM players WHERE Name = 'Paulo Dybala'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((kit, ty.as_str()), (10, "integer"));
+        let index: String = conn.query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'index'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(index, "players_Nationality_idx");
+
+        // a second load clashes unless it replaces the table
+        assert!(write_table(&mut conn, "assets/juventus.csv", &dialect, &opts).is_err());
+        let opts = SqliteOptions {
+            replace: true,
+            ..opts
+        };
+        assert_eq!(
+            write_table(&mut conn, "assets/juventus.csv", &dialect, &opts)?,
+            27
+        );
+        Ok(())
+    }
+}
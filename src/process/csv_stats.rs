@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+};
+
+use anyhow::Result;
+use csv::{Reader, StringRecord};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    csv_agg::{cell_value, compare},
+    csv_show::render_table,
+    CsvDialect,
+};
+use crate::utils::get_writer;
+
+/// The profile of one column. Mean and stddev are only set when every
+/// non-empty cell is a number.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ColumnStats {
+    pub column: String,
+    pub count: usize,
+    pub empty: usize,
+    pub distinct: usize,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub top: Vec<(String, usize)>,
+}
+
+/// Running state for one column. Mean and variance use Welford's method so
+/// they stay accurate in a single pass.
+#[derive(Debug, Default)]
+struct Profile {
+    count: usize,
+    empty: usize,
+    values: HashMap<String, usize>,
+    min: Option<String>,
+    max: Option<String>,
+    numeric: bool,
+    mean: f64,
+    m2: f64,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+}
+
+impl Profile {
+    fn new() -> Self {
+        Self {
+            numeric: true,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, cell: &str) {
+        self.count += 1;
+        if cell.trim().is_empty() {
+            self.empty += 1;
+            return;
+        }
+        let len = cell.chars().count();
+        self.min_length = Some(self.min_length.map_or(len, |m| m.min(len)));
+        self.max_length = Some(self.max_length.map_or(len, |m| m.max(len)));
+        let cell = cell.trim();
+        *self.values.entry(cell.to_string()).or_default() += 1;
+        if self.min.as_deref().is_none_or(|m| compare(cell, m).is_lt()) {
+            self.min = Some(cell.to_string());
+        }
+        if self.max.as_deref().is_none_or(|m| compare(cell, m).is_gt()) {
+            self.max = Some(cell.to_string());
+        }
+        // "inf" and "NaN" parse too, but would turn the mean into nonsense
+        match cell.parse::<f64>() {
+            Ok(v) if self.numeric && v.is_finite() => {
+                let n = (self.count - self.empty) as f64;
+                let delta = v - self.mean;
+                self.mean += delta / n;
+                self.m2 += delta * (v - self.mean);
+            }
+            _ => self.numeric = false,
+        }
+    }
+
+    fn finish(self, column: &str, top: usize) -> ColumnStats {
+        let n = self.count - self.empty;
+        let numeric = self.numeric && n > 0;
+        let mut values = self.values.into_iter().collect::<Vec<_>>();
+        values.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+        ColumnStats {
+            column: column.to_string(),
+            count: self.count,
+            empty: self.empty,
+            distinct: values.len(),
+            min: self.min.as_deref().map(cell_value),
+            max: self.max.as_deref().map(cell_value),
+            mean: numeric.then_some(self.mean),
+            // sample standard deviation, like most spreadsheets report
+            stddev: (numeric && n > 1).then(|| (self.m2 / (n - 1) as f64).sqrt()),
+            min_length: self.min_length,
+            max_length: self.max_length,
+            top: values.into_iter().take(top).collect(),
+        }
+    }
+}
+
+pub fn column_stats<R: Read>(
+    reader: &mut Reader<R>,
+    headers: &StringRecord,
+    top: usize,
+) -> Result<Vec<ColumnStats>> {
+    let mut profiles = headers.iter().map(|_| Profile::new()).collect::<Vec<_>>();
+    for record in reader.records() {
+        let record = record?;
+        for (i, profile) in profiles.iter_mut().enumerate() {
+            profile.add(record.get(i).unwrap_or(""));
+        }
+    }
+    Ok(headers
+        .iter()
+        .zip(profiles)
+        .map(|(name, profile)| profile.finish(name, top))
+        .collect())
+}
+
+/// Lays the stats out one column per row, for the terminal.
+pub fn render_stats(stats: &[ColumnStats]) -> String {
+    let headers = StringRecord::from(vec![
+        "column", "count", "empty", "distinct", "min", "max", "mean", "stddev", "min len",
+        "max len", "top",
+    ]);
+    let text = |v: &Option<Value>| match v {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+        None => String::new(),
+    };
+    let number = |v: Option<f64>| v.map_or(String::new(), |v| format!("{:.2}", v));
+    let length = |v: Option<usize>| v.map_or(String::new(), |v| v.to_string());
+    let rows = stats
+        .iter()
+        .map(|s| {
+            let top = s
+                .top
+                .iter()
+                .map(|(value, count)| format!("{} ({})", value, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            StringRecord::from(vec![
+                s.column.clone(),
+                s.count.to_string(),
+                s.empty.to_string(),
+                s.distinct.to_string(),
+                text(&s.min),
+                text(&s.max),
+                number(s.mean),
+                number(s.stddev),
+                length(s.min_length),
+                length(s.max_length),
+                top,
+            ])
+        })
+        .collect::<Vec<_>>();
+    render_table(&headers, &rows, 40)
+}
+
+pub fn process_csv_stats(
+    input: &str,
+    output: &str,
+    dialect: &CsvDialect,
+    top: usize,
+    json: bool,
+) -> Result<()> {
+    let mut reader = dialect.open(input)?;
+    let headers = dialect.headers(&mut reader)?;
+    let stats = column_stats(&mut reader, &headers, top)?;
+    let mut writer = get_writer(output)?;
+    if json {
+        serde_json::to_writer_pretty(&mut writer, &stats)?;
+        writer.write_all(b"\n")?;
+    } else {
+        writer.write_all(render_stats(&stats).as_bytes())?;
+    }
+    Ok(writer.flush()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_column_stats() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let data = "name,goals\nDybala,2\nPjanic,\nDybala,4\nKhedira,6\n";
+        let mut reader = dialect.reader(data.as_bytes());
+        let headers = dialect.headers(&mut reader)?;
+        let stats = column_stats(&mut reader, &headers, 1)?;
+
+        assert_eq!(stats[0].distinct, 3);
+        assert_eq!(stats[0].min, Some(json!("Dybala")));
+        assert_eq!(stats[0].mean, None);
+        assert_eq!(stats[0].top, vec![("Dybala".to_string(), 2)]);
+        assert_eq!(
+            (stats[0].min_length, stats[0].max_length),
+            (Some(6), Some(7))
+        );
+
+        assert_eq!((stats[1].count, stats[1].empty), (4, 1));
+        assert_eq!(
+            (stats[1].min.clone(), stats[1].max.clone()),
+            (Some(json!(2)), Some(json!(6)))
+        );
+        assert_eq!(stats[1].mean, Some(4.0));
+        assert_eq!(stats[1].stddev, Some(2.0));
+
+        let mut reader = dialect.reader(
+            &b"rating
+1.5
+NaN
+2.5
+"[..],
+        );
+        let headers = dialect.headers(&mut reader)?;
+        let stats = column_stats(&mut reader, &headers, 1)?;
+        assert_eq!((stats[0].mean, stats[0].stddev), (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn test_juventus_stats() -> Result<()> {
+        let dialect = CsvDialect::default();
+        let mut reader = dialect.reader_from_path("assets/juventus.csv")?;
+        let headers = dialect.headers(&mut reader)?;
+        let stats = column_stats(&mut reader, &headers, 3)?;
+        let table = render_stats(&stats);
+        assert!(table.lines().any(|l| l.starts_with("│ Kit Number ")));
+        assert_eq!(stats[3].top[0], ("Italy".to_string(), 8));
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use std::{iter::Peekable, str::Chars, str::FromStr};
+
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+use regex::Regex;
+
+/// One `--transform` rule. Either a change to a column, written
+/// `COLUMN:OP`, or a new column computed from others, written `NAME=EXPR`.
+///
+/// The ops are `trim`, `upper`, `lower`, `date=FORMAT`, `replace=/RE/TO/`
+/// and `split=/SEP/A,B,...`. `replace` and `split` take their arguments
+/// between a delimiter of your choice, the first character after `=`.
+#[derive(Debug, Clone)]
+pub enum TransformRule {
+    Apply { column: String, op: TransformOp },
+    Derive { name: String, expr: String },
+}
+
+#[derive(Debug, Clone)]
+pub enum TransformOp {
+    Trim,
+    Upper,
+    Lower,
+    /// Parses with a chrono format and writes ISO 8601. Text after the date,
+    /// like the age in `Apr 18, 1990 (29)`, is dropped.
+    Date(String),
+    Replace(Regex, String),
+    /// Splits on a separator into new columns; the last one takes whatever
+    /// is left, and missing pieces are empty.
+    Split(String, Vec<String>),
+}
+
+fn delimited(arg: &str, op: &str) -> Result<Vec<String>> {
+    let Some(delim) = arg.chars().next() else {
+        anyhow::bail!("{} needs its arguments, e.g. {}=/a/b/", op, op);
+    };
+    let mut parts = arg[delim.len_utf8()..]
+        .split(delim)
+        .map(String::from)
+        .collect::<Vec<_>>();
+    if parts.len() != 3 || !parts[2].is_empty() {
+        anyhow::bail!("{} takes two arguments, e.g. {}=/a/b/", op, op);
+    }
+    parts.pop();
+    Ok(parts)
+}
+
+impl FromStr for TransformOp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = s.split_once('=').unwrap_or((s, ""));
+        match (name.trim().to_lowercase().as_str(), arg) {
+            ("trim", "") => Ok(TransformOp::Trim),
+            ("upper", "") => Ok(TransformOp::Upper),
+            ("lower", "") => Ok(TransformOp::Lower),
+            ("date", fmt) if !fmt.is_empty() => Ok(TransformOp::Date(fmt.to_string())),
+            ("replace", arg) => {
+                let parts = delimited(arg, "replace")?;
+                Ok(TransformOp::Replace(
+                    Regex::new(&parts[0])?,
+                    parts[1].clone(),
+                ))
+            }
+            ("split", arg) => {
+                let parts = delimited(arg, "split")?;
+                let columns = parts[1]
+                    .split(',')
+                    .map(|c| c.trim().to_string())
+                    .collect::<Vec<_>>();
+                if parts[0].is_empty() || columns.iter().any(|c| c.is_empty()) {
+                    anyhow::bail!("split needs a separator and column names: {}", s);
+                }
+                Ok(TransformOp::Split(parts[0].clone(), columns))
+            }
+            _ => anyhow::bail!("Unsupported transform: {}", s),
+        }
+    }
+}
+
+impl FromStr for TransformRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let derive = match (s.find('='), s.find(':')) {
+            (Some(eq), Some(colon)) => eq < colon,
+            (eq, _) => eq.is_some(),
+        };
+        if derive {
+            let (name, expr) = s.split_once('=').expect("checked for =");
+            if name.trim().is_empty() {
+                anyhow::bail!("Derived column needs a name: {}", s);
+            }
+            return Ok(TransformRule::Derive {
+                name: name.trim().to_string(),
+                expr: expr.to_string(),
+            });
+        }
+        let Some((column, op)) = s.split_once(':') else {
+            anyhow::bail!("Use COLUMN:OP or NAME=EXPR, not {}", s);
+        };
+        Ok(TransformRule::Apply {
+            column: column.to_string(),
+            op: op.parse()?,
+        })
+    }
+}
+
+/// A derived column expression: `{Column}` references, numbers, quoted
+/// strings, `+ - * /` and parentheses. `+` concatenates unless both sides
+/// are numbers; an empty operand makes the result empty.
+#[derive(Debug, Clone, PartialEq)]
+enum Calc {
+    Column(usize),
+    Literal(String),
+    Neg(Box<Calc>),
+    Bin(char, Box<Calc>, Box<Calc>),
+}
+
+struct CalcParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    headers: &'a StringRecord,
+}
+
+impl CalcParser<'_> {
+    fn skip_ws(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn binary(&mut self, ops: &str, next: fn(&mut Self) -> Result<Calc>) -> Result<Calc> {
+        let mut calc = next(self)?;
+        loop {
+            self.skip_ws();
+            let Some(op) = self.chars.next_if(|c| ops.contains(*c)) else {
+                return Ok(calc);
+            };
+            calc = Calc::Bin(op, Box::new(calc), Box::new(next(self)?));
+        }
+    }
+
+    fn sum(&mut self) -> Result<Calc> {
+        self.binary("+-", Self::product)
+    }
+
+    fn product(&mut self) -> Result<Calc> {
+        self.binary("*/", Self::factor)
+    }
+
+    fn factor(&mut self) -> Result<Calc> {
+        self.skip_ws();
+        match self.chars.next() {
+            Some('-') => Ok(Calc::Neg(Box::new(self.factor()?))),
+            Some('(') => {
+                let calc = self.sum()?;
+                self.skip_ws();
+                match self.chars.next() {
+                    Some(')') => Ok(calc),
+                    _ => anyhow::bail!("Missing ) in derived column"),
+                }
+            }
+            Some('{') => {
+                let name = self.until('}')?;
+                match self.headers.iter().position(|h| h == name) {
+                    Some(idx) => Ok(Calc::Column(idx)),
+                    None => anyhow::bail!("Unknown column in derived column: {}", name),
+                }
+            }
+            Some(quote @ ('"' | '\'')) => Ok(Calc::Literal(self.until(quote)?)),
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                Ok(Calc::Literal(number))
+            }
+            Some(c) => anyhow::bail!("Unexpected {:?} in derived column", c),
+            None => anyhow::bail!("Unexpected end of derived column"),
+        }
+    }
+
+    fn until(&mut self, end: char) -> Result<String> {
+        let mut s = String::new();
+        for c in self.chars.by_ref() {
+            if c == end {
+                return Ok(s);
+            }
+            s.push(c);
+        }
+        anyhow::bail!("Missing {} in derived column", end)
+    }
+}
+
+impl Calc {
+    fn parse(src: &str, headers: &StringRecord) -> Result<Self> {
+        let mut parser = CalcParser {
+            chars: src.chars().peekable(),
+            headers,
+        };
+        let calc = parser.sum()?;
+        parser.skip_ws();
+        if let Some(c) = parser.chars.next() {
+            anyhow::bail!("Unexpected {:?} in derived column: {}", c, src);
+        }
+        Ok(calc)
+    }
+
+    fn eval(&self, record: &StringRecord) -> Result<String> {
+        let (op, a, b) = match self {
+            Calc::Column(idx) => return Ok(record.get(*idx).unwrap_or("").to_string()),
+            Calc::Literal(s) => return Ok(s.clone()),
+            Calc::Neg(calc) => {
+                let value = calc.eval(record)?;
+                return arith('-', "0", &value);
+            }
+            Calc::Bin(op, a, b) => (*op, a.eval(record)?, b.eval(record)?),
+        };
+        arith(op, &a, &b)
+    }
+}
+
+fn arith(op: char, a: &str, b: &str) -> Result<String> {
+    if a.is_empty() || b.is_empty() {
+        return Ok(String::new());
+    }
+    let (x, y) = (a.trim(), b.trim());
+    if let (Ok(x), Ok(y)) = (x.parse::<i64>(), y.parse::<i64>()) {
+        let int = match op {
+            '+' => x.checked_add(y),
+            '-' => x.checked_sub(y),
+            '*' => x.checked_mul(y),
+            _ => None,
+        };
+        if let Some(int) = int {
+            return Ok(int.to_string());
+        }
+    }
+    match (x.parse::<f64>(), y.parse::<f64>()) {
+        (Ok(_), Ok(y)) if op == '/' && y == 0.0 => anyhow::bail!("Division by zero"),
+        (Ok(x), Ok(y)) => Ok(match op {
+            '+' => x + y,
+            '-' => x - y,
+            '*' => x * y,
+            _ => x / y,
+        }
+        .to_string()),
+        _ if op == '+' => Ok(format!("{}{}", a, b)),
+        _ => anyhow::bail!("Can't compute {:?} {} {:?}", a, op, b),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Apply(usize, TransformOp),
+    Derive(Calc),
+}
+
+/// Transform rules resolved against a header, ready to run on each record.
+/// New columns from `split` and derived rules go at the end, in rule order,
+/// and later rules may use them.
+#[derive(Debug, Clone)]
+pub struct Transforms {
+    steps: Vec<Step>,
+    headers: StringRecord,
+    /// Fields in the input header.
+    width: usize,
+}
+
+impl Transforms {
+    pub fn new(headers: &StringRecord, rules: &[TransformRule]) -> Result<Self> {
+        let width = headers.len();
+        let mut headers = headers.clone();
+        let mut steps = Vec::new();
+        for rule in rules {
+            match rule {
+                TransformRule::Apply { column, op } => {
+                    let Some(idx) = headers.iter().position(|h| h == column) else {
+                        anyhow::bail!("Unknown column in --transform: {}", column);
+                    };
+                    if let TransformOp::Split(_, columns) = op {
+                        for c in columns {
+                            check_new_column(&headers, c)?;
+                            headers.push_field(c);
+                        }
+                    }
+                    steps.push(Step::Apply(idx, op.clone()));
+                }
+                TransformRule::Derive { name, expr } => {
+                    check_new_column(&headers, name)?;
+                    steps.push(Step::Derive(Calc::parse(expr, &headers)?));
+                    headers.push_field(name);
+                }
+            }
+        }
+        Ok(Self {
+            steps,
+            headers,
+            width,
+        })
+    }
+
+    /// The input header followed by any columns the rules add.
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    pub fn apply(&self, record: StringRecord) -> Result<StringRecord> {
+        if self.steps.is_empty() {
+            return Ok(record);
+        }
+        let position = record.position().cloned();
+        let line = || position.as_ref().map_or(0, |p| p.line());
+        if record.len() > self.width {
+            anyhow::bail!(
+                "Line {}, --transform: expected {} fields, found {}",
+                line(),
+                self.width,
+                record.len()
+            );
+        }
+        let mut cells = record.iter().map(String::from).collect::<Vec<_>>();
+        // short rows of a --flexible input are padded, so the columns the
+        // rules add land where the header says
+        cells.resize(self.width, String::new());
+        for step in &self.steps {
+            let result = match step {
+                Step::Apply(idx, op) => apply_op(op, *idx, &mut cells),
+                Step::Derive(calc) => calc
+                    .eval(&cells.iter().collect())
+                    .map(|value| cells.push(value)),
+            };
+            result.map_err(|e| anyhow::anyhow!("Line {}, --transform: {}", line(), e))?;
+        }
+        let mut record = StringRecord::from(cells);
+        record.set_position(position);
+        Ok(record)
+    }
+}
+
+fn check_new_column(headers: &StringRecord, name: &str) -> Result<()> {
+    if headers.iter().any(|h| h == name) {
+        anyhow::bail!("Column already exists in --transform: {}", name);
+    }
+    Ok(())
+}
+
+fn apply_op(op: &TransformOp, idx: usize, cells: &mut Vec<String>) -> Result<()> {
+    let cell = &cells[idx];
+    let value = match op {
+        TransformOp::Trim => cell.trim().to_string(),
+        TransformOp::Upper => cell.to_uppercase(),
+        TransformOp::Lower => cell.to_lowercase(),
+        TransformOp::Date(_) if cell.trim().is_empty() => String::new(),
+        TransformOp::Date(fmt) => parse_date(cell.trim(), fmt)?,
+        TransformOp::Replace(re, to) => re.replace_all(cell, to.as_str()).into_owned(),
+        TransformOp::Split(sep, columns) => {
+            let mut pieces = cell
+                .splitn(columns.len(), sep.as_str())
+                .map(String::from)
+                .collect::<Vec<_>>();
+            pieces.resize(columns.len(), String::new());
+            cells.extend(pieces);
+            return Ok(());
+        }
+    };
+    cells[idx] = value;
+    Ok(())
+}
+
+fn parse_date(cell: &str, fmt: &str) -> Result<String> {
+    if let Ok((datetime, _)) = NaiveDateTime::parse_and_remainder(cell, fmt) {
+        return Ok(datetime.format("%Y-%m-%dT%H:%M:%S").to_string());
+    }
+    match NaiveDate::parse_and_remainder(cell, fmt) {
+        Ok((date, _)) => Ok(date.format("%Y-%m-%d").to_string()),
+        Err(e) => anyhow::bail!("Can't read {:?} as a date with {:?}: {}", cell, fmt, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(rules: &[&str], headers: &[&str], row: &[&str]) -> Result<(Vec<String>, Vec<String>)> {
+        let rules = rules
+            .iter()
+            .map(|r| r.parse())
+            .collect::<Result<Vec<TransformRule>>>()?;
+        let transforms = Transforms::new(&StringRecord::from(headers.to_vec()), &rules)?;
+        let record = transforms.apply(StringRecord::from(row.to_vec()))?;
+        Ok((
+            transforms.headers().iter().map(String::from).collect(),
+            record.iter().map(String::from).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_date_case_and_replace() -> Result<()> {
+        let (_, row) = run(
+            &[
+                "DOB:date=%b %d, %Y",
+                "Name:trim",
+                "Name:upper",
+                "Position:replace=/^(\\w)\\w+/$1/",
+            ],
+            &["Name", "Position", "DOB"],
+            &[" Wojciech Szczesny ", "Goalkeeper", "Apr 18, 1990 (29)"],
+        )?;
+        assert_eq!(row, vec!["WOJCIECH SZCZESNY", "G", "1990-04-18"]);
+        assert!(run(&["DOB:date=%b %d, %Y"], &["DOB"], &["soon"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_and_derive() -> Result<()> {
+        let (headers, row) = run(
+            &[
+                "Name:split=/ /First,Last/",
+                "Label={Last} + ' #' + {Kit Number}",
+                "Double={Kit Number} * 2",
+                "Half=({Kit Number} + 0) / 4",
+            ],
+            &["Name", "Kit Number"],
+            &["Paulo Dybala", "10"],
+        )?;
+        assert_eq!(
+            headers,
+            vec![
+                "Name",
+                "Kit Number",
+                "First",
+                "Last",
+                "Label",
+                "Double",
+                "Half"
+            ]
+        );
+        assert_eq!(
+            row,
+            vec![
+                "Paulo Dybala",
+                "10",
+                "Paulo",
+                "Dybala",
+                "Dybala #10",
+                "20",
+                "2.5"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        assert!("Name:shout".parse::<TransformRule>().is_err());
+        assert!("Name:replace=/a/".parse::<TransformRule>().is_err());
+        assert!("Name".parse::<TransformRule>().is_err());
+        assert!(run(&["Club:trim"], &["Name"], &["x"]).is_err());
+        assert!(run(&["X={Name} - 1"], &["Name"], &["x"]).is_err());
+        assert!(run(&["Name={Name} + 'x'"], &["Name"], &["x"]).is_err());
+        assert!(run(&["Name:split=/ /First,Name/"], &["Name"], &["x"]).is_err());
+    }
+
+    #[test]
+    fn test_ragged_rows() -> Result<()> {
+        // what a --flexible reader hands over for short and long rows
+        let rules = ["Name:split=/ /First,Last/", "Label={Last}"];
+        let headers = ["Name", "Kit Number", "Club"];
+        let (_, row) = run(&rules, &headers, &["Paulo Dybala"])?;
+        assert_eq!(
+            row,
+            vec!["Paulo Dybala", "", "", "Paulo", "Dybala", "Dybala"]
+        );
+        assert!(run(&rules, &headers, &["Paulo Dybala", "10", "Juve", "x"]).is_err());
+        Ok(())
+    }
+}
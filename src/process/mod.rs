@@ -1,12 +1,52 @@
 mod b64;
+mod csv_agg;
+mod csv_codegen;
 mod csv_convert;
+mod csv_crypt;
+mod csv_dialect;
+mod csv_diff;
+mod csv_encoding;
+mod csv_expr;
+mod csv_fake;
+mod csv_from;
+mod csv_infer;
+mod csv_join;
+mod csv_mask;
+mod csv_nested;
+mod csv_output;
+mod csv_query;
+mod csv_reject;
+mod csv_schema;
+mod csv_select;
+mod csv_show;
+mod csv_split;
+mod csv_sqlite;
+mod csv_stats;
+mod csv_transform;
 mod gen_pass;
 mod http_serve;
 mod jwt;
 mod text;
 
 pub use b64::{process_decode, process_encode, process_generate_decode, process_generate_encode};
-pub use csv_convert::process_csv;
+pub use csv_agg::{process_csv_agg, AggOptions};
+pub use csv_codegen::process_csv_codegen;
+pub use csv_convert::{process_csv, ConvertOptions};
+pub use csv_crypt::{process_csv_decrypt, process_csv_encrypt, CryptOptions};
+pub use csv_dialect::CsvDialect;
+pub use csv_diff::process_csv_diff;
+pub use csv_fake::process_csv_fake;
+pub use csv_from::process_csv_from;
+pub use csv_infer::TypeHints;
+pub use csv_join::{process_csv_join, JoinOptions};
+pub use csv_mask::{process_csv_mask, MaskOptions};
+pub use csv_query::process_csv_query;
+pub use csv_schema::{process_csv_schema, process_csv_validate};
+pub use csv_show::{process_csv_show, RowWindow};
+pub use csv_split::{process_csv_merge, process_csv_split, SplitBy};
+pub use csv_sqlite::{process_csv_to_sqlite, SqliteOptions};
+pub use csv_stats::process_csv_stats;
+pub use csv_transform::TransformRule;
 pub use gen_pass::process_genpass;
 pub use http_serve::process_http_serve;
 pub use jwt::{process_jwt_sign, process_jwt_verify};
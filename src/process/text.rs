@@ -11,7 +11,10 @@ use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 
 use bincode::{deserialize, serialize};
-use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, Payload},
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+};
 use serde::{Deserialize, Serialize};
 
 use super::gen_pass::{self, genpass_length};
@@ -24,7 +27,7 @@ trait TextVerify {
     fn verify(&self, reader: &mut dyn Read, sign: &[u8]) -> Result<bool>;
 }
 
-trait KeyLoader {
+pub(crate) trait KeyLoader {
     fn load<T>(path: T) -> Result<Self>
     where
         T: AsRef<Path>,
@@ -35,7 +38,7 @@ trait KeyGenerator {
     fn generate() -> Result<Vec<Vec<u8>>>;
 }
 
-struct Blake3 {
+pub(crate) struct Blake3 {
     key: [u8; 32],
 }
 
@@ -78,10 +81,23 @@ impl Blake3 {
     }
 
     pub fn try_new(key: &[u8]) -> Result<Self> {
-        let key = &key[..32];
+        let Some(key) = key.get(..32) else {
+            anyhow::bail!("Blake3 key must be at least 32 bytes");
+        };
         let key = key.try_into()?;
         Ok(Blake3::new(key))
     }
+
+    pub fn keyed_hash(&self, data: &[u8]) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, data)
+    }
+
+    /// An endless keyed stream of bytes derived from `data`.
+    pub fn keyed_stream(&self, data: &[u8]) -> blake3::OutputReader {
+        blake3::Hasher::new_keyed(&self.key)
+            .update(data)
+            .finalize_xof()
+    }
 }
 
 impl KeyLoader for Blake3 {
@@ -252,10 +268,10 @@ impl Cha1305Processor {
         Ok(Self::new(ChaCha20Poly1305::new(key), nonce))
     }
 
-    fn try_load(key_path: &str) -> Result<Self> {
+    pub(crate) fn try_load(key_path: &str) -> Result<Self> {
         let key = fs::read(key_path)?;
         if key.len() < 32 {
-            panic!("key must be 32 bytes");
+            anyhow::bail!("key must be 32 bytes");
         }
         let key = key[..32].to_vec();
         let nonce = genpass_length(12)?;
@@ -270,6 +286,32 @@ impl Cha1305Processor {
         let key = key[..32].to_vec();
         Self::try_new(&key, nonce)
     }
+
+    /// Encrypts under a fresh random nonce, authenticating `aad` with the
+    /// message so it only decrypts given the same `aad`.
+    pub(crate) fn encrypt_aad(&self, input: &[u8], aad: &[u8]) -> Result<Cha1305Resp> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let message = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: input, aad })
+            .map_err(|_| anyhow::anyhow!("encryption failure!"))?;
+        Ok(Cha1305Resp::new(message, nonce.to_vec()))
+    }
+
+    pub(crate) fn decrypt_aad(&self, resp: &Cha1305Resp, aad: &[u8]) -> Result<Vec<u8>> {
+        if resp.nonce.len() != 12 {
+            anyhow::bail!("nonce must be 12 bytes");
+        }
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&resp.nonce),
+                Payload {
+                    msg: &resp.message,
+                    aad,
+                },
+            )
+            .map_err(|_| anyhow::anyhow!("decryption failure!"))
+    }
 }
 impl Cha1305Encrypt for Cha1305Processor {
     fn encrypt(&self, input: Vec<u8>) -> Result<Cha1305Resp> {
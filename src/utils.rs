@@ -1,5 +1,9 @@
 use anyhow::Result;
-use std::{fs, fs::File, io::Read};
+use std::{
+    fs,
+    fs::File,
+    io::{BufWriter, Read, Write},
+};
 
 pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     let reader: Box<dyn Read> = if input == "-" {
@@ -10,6 +14,15 @@ pub fn get_reader(input: &str) -> Result<Box<dyn Read>> {
     Ok(reader)
 }
 
+pub fn get_writer(output: &str) -> Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if output == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(BufWriter::new(File::create(output)?))
+    };
+    Ok(writer)
+}
+
 pub fn get_vec(input: &str) -> Result<Vec<u8>> {
     if input == "-" {
         let mut reader = std::io::stdin();